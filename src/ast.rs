@@ -1,10 +1,130 @@
+use std::{fmt, rc::Rc};
+
 use crate::tokens::Location;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum NodeKind {
+    Nil,
     String(String),
-    // FunctionCall -> Name, Arguments
-    FunctionCall(String, Vec<Node>),
+    Number(f64),
+    Boolean(bool),
+    Identifier(String),
+    // Let -> Name, Value
+    Let(String, Box<Node>),
+    // Assign -> Target (an Identifier or Index), Value
+    Assign(Box<Node>, Box<Node>),
+    Block(Vec<Node>),
+    // If -> (Condition, Block) for the `if` and each `elseif`, `else` Block
+    If(Vec<(Node, Node)>, Option<Box<Node>>),
+    // While -> Condition, Block
+    While(Box<Node>, Box<Node>),
+    // NumericFor -> Variable name, Start, Stop, Step, Block
+    NumericFor(String, Box<Node>, Box<Node>, Option<Box<Node>>, Box<Node>),
+    // GenericFor -> Variable names, Iterator expressions, Block
+    GenericFor(Vec<String>, Vec<Node>, Box<Node>),
+    Break,
+    Continue,
+    // FunctionDefinition -> Name, Parameter names, Block
+    FunctionDefinition(String, Vec<String>, Rc<Node>),
+    // Function -> Parameter names, Block
+    Function(Vec<String>, Rc<Node>),
+    // Return -> Values
+    Return(Vec<Node>),
+    // Table -> Entries, keys are None for positional entries
+    Table(Vec<(Option<Node>, Node)>),
+    // Interpolation -> Parts, string literals and expressions converted with tostring
+    Interpolation(Vec<Node>),
+    // Index -> Table, Key
+    Index(Box<Node>, Box<Node>),
+    // FunctionCall -> Function, Arguments
+    FunctionCall(Box<Node>, Vec<Node>),
+    // MethodCall -> Object, Method name, Arguments
+    MethodCall(Box<Node>, String, Vec<Node>),
+    // BinaryOp -> Operator, Left, Right
+    BinaryOp(BinaryOperator, Box<Node>, Box<Node>),
+    // UnaryOp -> Operator, Operand
+    UnaryOp(UnaryOperator, Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    And,
+    Or,
+
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl BinaryOperator {
+    /// Binding power of the operator, higher binds tighter
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+
+            BinaryOperator::Equal | BinaryOperator::NotEqual => 3,
+
+            BinaryOperator::Less
+            | BinaryOperator::LessEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual => 4,
+
+            BinaryOperator::Add | BinaryOperator::Subtract => 5,
+
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => 6,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::And => "and",
+            BinaryOperator::Or => "or",
+
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+    Length,
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnaryOperator::Negate => write!(f, "-"),
+            UnaryOperator::Not => write!(f, "not"),
+            UnaryOperator::Length => write!(f, "#"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
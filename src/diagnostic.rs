@@ -0,0 +1,443 @@
+use std::fmt;
+
+use crate::{error_code::ErrorCode, tokens::Location};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+/// A location related to a diagnostic, e.g. "function defined here"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub location: Location,
+    pub message: String,
+}
+
+/// An error or warning found in the code, reported by whoever embeds the lexer, parser and
+/// interpreter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: ErrorCode,
+    pub message: String,
+    // Where the problem is
+    pub location: Location,
+    pub secondary_labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    // Replacements for the code at `location`, e.g. a similar name for a misspelled one
+    pub suggestions: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error<T: Into<String>>(code: ErrorCode, message: T, location: Location) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            location,
+            secondary_labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn with_label<T: Into<String>>(mut self, location: Location, message: T) -> Self {
+        self.secondary_labels.push(Label {
+            location,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note<T: Into<String>>(mut self, note: T) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help<T: Into<String>>(mut self, help: T) -> Self {
+        self.help.push(help.into());
+        self
+    }
+}
+
+/// Note for the last error reported before giving up, e.g. because of `--max-errors`
+pub fn error_limit_note(max_errors: usize) -> String {
+    format!("Stopped after reaching the limit of {} errors", max_errors)
+}
+
+// Lines shown before and after the lines of the main location
+const CONTEXT_LINES: usize = 1;
+// Spans across more lines are shown with the lines in between left out
+const MAX_SPAN_LINES: usize = 4;
+
+// ANSI escape codes
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+/// Turns diagnostics into text showing the source code they point at, e.g.
+///
+/// ```text
+/// [Error M0005]
+/// Invalid digit 'a' in number literal
+///
+/// [Location] main.min:2:11
+///    |
+///  1 | let a = 1;
+///  2 | let b = 12abc;
+///    |           ^
+///  3 | print(a, b);
+/// ```
+pub struct Renderer<'a> {
+    source_code: &'a str,
+    source_lines: Vec<&'a str>,
+    // Byte offset where each line starts, for the JSON output
+    line_start_offsets: Vec<usize>,
+    // Whether to use ANSI colours, only for terminals
+    colour: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(source_code: &'a str) -> Self {
+        Self {
+            source_code,
+            line_start_offsets: std::iter::once(0)
+                .chain(source_code.match_indices('\n').map(|(index, _)| index + 1))
+                .collect(),
+            // `lines` leaves out the empty line after a trailing newline, unlike the lexer
+            source_lines: source_code.lines().collect(),
+            colour: false,
+        }
+    }
+
+    pub fn with_colour(mut self, colour: bool) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut output = String::new();
+
+        let severity_colour = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        output += &format!(
+            "{}\n{}\n\n",
+            self.paint(
+                &format!("[{} {}]", diagnostic.severity, diagnostic.code),
+                severity_colour
+            ),
+            self.paint(&diagnostic.message, BOLD)
+        );
+        self.render_location(
+            &mut output,
+            &diagnostic.location,
+            None,
+            '^',
+            RED,
+            CONTEXT_LINES,
+        );
+
+        for label in diagnostic.secondary_labels.iter() {
+            output.push('\n');
+            self.render_location(
+                &mut output,
+                &label.location,
+                Some(&label.message),
+                '-',
+                BLUE,
+                0,
+            );
+        }
+
+        for note in diagnostic.notes.iter() {
+            output += &format!("\n{}\n{}\n", self.paint("[Note]", CYAN), note);
+        }
+
+        for suggestion in diagnostic.suggestions.iter() {
+            output += &format!(
+                "\n{}\nDid you mean '{}'?\n",
+                self.paint("[Help]", CYAN),
+                suggestion
+            );
+        }
+
+        for help in diagnostic.help.iter() {
+            output += &format!("\n{}\n{}\n", self.paint("[Help]", CYAN), help);
+        }
+
+        output
+    }
+
+    /// Renders the diagnostic as a single line JSON object, for tools rather than people, e.g.
+    /// `{"file":"main.min","byte_start":10,"byte_end":11,"line_start":1,...,"help":[],"suggestions":[]}`
+    pub fn render_json(&self, diagnostic: &Diagnostic) -> String {
+        let labels = diagnostic
+            .secondary_labels
+            .iter()
+            .map(|label| {
+                format!(
+                    "{{{},\"message\":{}}}",
+                    self.json_span(&label.location),
+                    json_string(&label.message)
+                )
+            })
+            .collect::<Vec<String>>();
+
+        format!(
+            "{{{},\"severity\":{},\"code\":{},\"message\":{},\"labels\":[{}],\"notes\":[{}],\"help\":[{}],\"suggestions\":[{}]}}",
+            self.json_span(&diagnostic.location),
+            json_string(&diagnostic.severity.to_string().to_lowercase()),
+            json_string(diagnostic.code.code()),
+            json_string(&diagnostic.message),
+            labels.join(","),
+            json_strings(&diagnostic.notes),
+            json_strings(&diagnostic.help),
+            json_strings(&diagnostic.suggestions),
+        )
+    }
+
+    /// The JSON fields for where `location` is. Lines and columns start at 1 and end at the
+    /// last character, while byte offsets start at 0 and end after the last character.
+    fn json_span(&self, location: &Location) -> String {
+        // Columns of different lines can't be compared, only a span on one line can't end
+        // before it starts
+        let end_col = if location.start_line == location.end_line {
+            location.end_col.max(location.start_col)
+        } else {
+            location.end_col
+        };
+
+        format!(
+            "\"file\":{},\"byte_start\":{},\"byte_end\":{},\"line_start\":{},\"line_end\":{},\"column_start\":{},\"column_end\":{}",
+            json_string(&location.file_path),
+            self.byte_offset(location.start_line, location.start_col),
+            self.byte_offset(location.end_line, end_col + 1),
+            location.start_line,
+            location.end_line,
+            location.start_col,
+            location.end_col,
+        )
+    }
+
+    /// Byte offset of the character at `col` in `line_number`, or of the end of the line if it
+    /// is shorter
+    fn byte_offset(&self, line_number: usize, col: usize) -> usize {
+        let Some(&line_start) = self.line_start_offsets.get(line_number - 1) else {
+            return self.source_code.len();
+        };
+
+        let line = self.source_code[line_start..]
+            .split('\n')
+            .next()
+            .unwrap_or_default();
+
+        line_start
+            + line
+                .char_indices()
+                .nth(col - 1)
+                .map(|(index, _)| index)
+                .unwrap_or(line.len())
+    }
+
+    /// Shows the lines of `location` with its span underlined by `marker`, and `label` after it
+    fn render_location(
+        &self,
+        output: &mut String,
+        location: &Location,
+        label: Option<&str>,
+        marker: char,
+        marker_colour: &str,
+        context_lines: usize,
+    ) {
+        // Line numbers of the span, `None` where the middle of a long one is left out
+        let mut span_lines = (location.start_line..=location.end_line)
+            .map(Some)
+            .collect::<Vec<Option<usize>>>();
+        if span_lines.len() > MAX_SPAN_LINES {
+            let last_lines = span_lines.split_off(span_lines.len() - MAX_SPAN_LINES / 2);
+            span_lines.truncate(MAX_SPAN_LINES / 2);
+            span_lines.push(None);
+            span_lines.extend(last_lines);
+        }
+
+        // Context lines only exist in the source code, not past its end
+        let first_line = location
+            .start_line
+            .saturating_sub(context_lines)
+            .max(1)
+            .min(location.start_line);
+        let last_line = (location.end_line + context_lines)
+            .min(self.source_lines.len())
+            .max(location.end_line);
+
+        let gutter_width = last_line.to_string().len();
+        let empty_gutter = self.paint(&format!(" {} |", " ".repeat(gutter_width)), BLUE);
+
+        *output += &format!(
+            "{} {}:{}:{}\n",
+            self.paint("[Location]", BLUE),
+            location.file_path,
+            location.start_line,
+            location.start_col
+        );
+        *output += &format!("{}\n", empty_gutter);
+
+        for line_number in first_line..location.start_line {
+            self.render_line(
+                output,
+                line_number,
+                self.source_line(line_number),
+                gutter_width,
+            );
+        }
+
+        for (index, &line_number) in span_lines.iter().enumerate() {
+            let Some(line_number) = line_number else {
+                *output += &format!("{}\n", self.paint("...", BLUE));
+                continue;
+            };
+
+            let line = if line_number == location.start_line {
+                // Spans past the end of the source code still have the line they start at
+                self.source_lines
+                    .get(line_number - 1)
+                    .copied()
+                    .unwrap_or(&location.line)
+            } else {
+                self.source_line(line_number)
+            };
+            self.render_line(output, line_number, line, gutter_width);
+
+            let line_length = line.chars().count();
+            let indentation = line.chars().take_while(|c| c.is_whitespace()).count();
+
+            let start_col = if line_number == location.start_line {
+                location.start_col
+            } else {
+                indentation + 1
+            };
+            let end_col = if line_number == location.end_line {
+                location.end_col
+            } else {
+                line_length
+            }
+            .max(start_col);
+
+            // Nothing to underline on empty lines in the middle of a span
+            if line_number != location.start_line
+                && line_number != location.end_line
+                && indentation == line_length
+            {
+                continue;
+            }
+
+            let is_last = index + 1 == span_lines.len();
+            *output += &format!(
+                "{} {}{}{}\n",
+                empty_gutter,
+                // Tabs are kept so that the markers line up with the line above
+                line.chars()
+                    .chain(std::iter::repeat(' '))
+                    .take(start_col - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect::<String>(),
+                self.paint(
+                    &marker.to_string().repeat(end_col - start_col + 1),
+                    marker_colour
+                ),
+                match label {
+                    Some(label) if is_last => format!(" {}", self.paint(label, marker_colour)),
+                    _ => String::new(),
+                }
+            );
+        }
+
+        for line_number in location.end_line + 1..=last_line {
+            self.render_line(
+                output,
+                line_number,
+                self.source_line(line_number),
+                gutter_width,
+            );
+        }
+    }
+
+    fn render_line(
+        &self,
+        output: &mut String,
+        line_number: usize,
+        line: &str,
+        gutter_width: usize,
+    ) {
+        let gutter = self.paint(
+            &format!(" {:>width$} |", line_number, width = gutter_width),
+            BLUE,
+        );
+
+        if line.is_empty() {
+            *output += &format!("{}\n", gutter);
+        } else {
+            *output += &format!("{} {}\n", gutter, line);
+        }
+    }
+
+    fn source_line(&self, line_number: usize) -> &str {
+        self.source_lines
+            .get(line_number - 1)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn paint(&self, text: &str, colour: &str) -> String {
+        if self.colour {
+            format!("{}{}{}", colour, text, RESET)
+        } else {
+            String::from(text)
+        }
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\r' => json += "\\r",
+            '\t' => json += "\\t",
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+fn json_strings(texts: &[String]) -> String {
+    texts
+        .iter()
+        .map(|text| json_string(text))
+        .collect::<Vec<String>>()
+        .join(",")
+}
@@ -0,0 +1,136 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::value::{Function, Value};
+
+/// A lexical scope, variables not found in it are looked up in its parent
+#[derive(Debug, Default)]
+pub struct Environment {
+    // Every variable declared with a name, oldest first, with the number of declarations made
+    // in this scope when it was declared. Redeclaring a name adds a new variable, like Lua's
+    // `local`, so closures created before keep seeing the old one.
+    variables: HashMap<String, Vec<(usize, Value)>>,
+    declarations: usize,
+    // The parent, with the number of its declarations visible from this scope
+    parent: Option<(Rc<RefCell<Environment>>, usize)>,
+}
+
+impl Environment {
+    /// A scope inside `parent` that sees every variable declared in it so far
+    pub fn new(parent: Option<Rc<RefCell<Environment>>>) -> Rc<RefCell<Self>> {
+        let declarations = parent
+            .as_ref()
+            .map_or(0, |parent| parent.borrow().declarations);
+
+        Self::new_at(parent, declarations)
+    }
+
+    /// A scope inside `parent` as it was after `declarations` declarations, e.g. for calling a
+    /// closure. Variables declared after that are still seen, unless they redeclare a name.
+    pub fn new_at(
+        parent: Option<Rc<RefCell<Environment>>>,
+        declarations: usize,
+    ) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            variables: HashMap::new(),
+            declarations: 0,
+            parent: parent.map(|parent| (parent, declarations)),
+        }))
+    }
+
+    /// How many variables have been declared in this scope, see `new_at`
+    pub fn declarations(&self) -> usize {
+        self.declarations
+    }
+
+    /// Declares a variable in this scope, shadowing any variable with the same name
+    pub fn declare<T: Into<String>>(&mut self, variable_name: T, value: Value) {
+        let value = self.without_own_closure(value);
+
+        self.declarations += 1;
+        self.variables
+            .entry(variable_name.into())
+            .or_default()
+            .push((self.declarations, value));
+    }
+
+    pub fn get(&self, variable_name: &str) -> Option<Value> {
+        self.get_at(variable_name, self.declarations)
+    }
+
+    fn get_at(&self, variable_name: &str, declarations: usize) -> Option<Value> {
+        match self.variable_index(variable_name, declarations) {
+            Some(index) => match &self.variables[variable_name][index].1 {
+                // The function was stored without its closure, which is this very environment
+                Value::Function(Function::User(function, None)) => Some(Value::Function(
+                    Function::User(function.clone(), function.closure.upgrade()),
+                )),
+                value => Some(value.clone()),
+            },
+            None => self.parent.as_ref().and_then(|(parent, declarations)| {
+                parent.borrow().get_at(variable_name, *declarations)
+            }),
+        }
+    }
+
+    /// Index of the variable named `variable_name` seen after `declarations` declarations, the
+    /// newest one declared by then, or the newest one if all were declared later
+    fn variable_index(&self, variable_name: &str, declarations: usize) -> Option<usize> {
+        let variables = self.variables.get(variable_name)?;
+
+        variables
+            .iter()
+            .rposition(|(declared_at, _)| *declared_at <= declarations)
+            .or(variables.len().checked_sub(1))
+    }
+
+    /// Names of every variable visible from this scope, including the ones of its parents
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .parent
+            .as_ref()
+            .map(|(parent, _)| parent.borrow().names())
+            .unwrap_or_default();
+        names.extend(self.variables.keys().cloned());
+
+        names
+    }
+
+    /// Drops the closure of a function created in this environment, since the environment
+    /// keeping alive the function keeping alive the environment would never be freed. `get`
+    /// gives it back, as the environment is alive when the function is read from it.
+    ///
+    /// Other cycles are still never freed, e.g. a function stored in a table in the
+    /// environment it captures, or in a variable of a parent of that environment.
+    fn without_own_closure(&self, value: Value) -> Value {
+        match value {
+            Value::Function(Function::User(function, Some(closure)))
+                if std::ptr::eq(closure.as_ptr(), self) =>
+            {
+                Value::Function(Function::User(function, None))
+            }
+            other => other,
+        }
+    }
+
+    /// Assigns to the closest variable with this name, returns false if there is none
+    pub fn assign(&mut self, variable_name: &str, value: Value) -> bool {
+        self.assign_at(variable_name, value, self.declarations)
+    }
+
+    fn assign_at(&mut self, variable_name: &str, value: Value, declarations: usize) -> bool {
+        if let Some(index) = self.variable_index(variable_name, declarations) {
+            let value = self.without_own_closure(value);
+            self.variables.get_mut(variable_name).unwrap()[index].1 = value;
+            return true;
+        }
+
+        match &self.parent {
+            Some((parent, declarations)) => {
+                parent
+                    .borrow_mut()
+                    .assign_at(variable_name, value, *declarations)
+            }
+            None => false,
+        }
+    }
+}
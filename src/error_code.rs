@@ -0,0 +1,322 @@
+use std::fmt;
+
+/// A stable identifier for each kind of error, shown with the error and explained in more
+/// detail by `minute --explain M0102`. Syntax errors are `M00xx`, runtime errors are `M01xx`,
+/// and the codes never change meaning once released.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    // Lexer
+    UnexpectedCharacter,
+    UnterminatedString,
+    UnterminatedComment,
+    UnclosedInterpolation,
+    InvalidNumber,
+    InvalidEscape,
+
+    // Parser
+    UnexpectedToken,
+    UnexpectedEndOfFile,
+    ReturnOutsideFunction,
+    LoopControlOutsideLoop,
+    InvalidAssignment,
+    ExpectedStatement,
+    DuplicateParameter,
+    EmptyInterpolation,
+    InvalidNumericFor,
+    NestingTooDeep,
+
+    // Interpreter
+    InternalError,
+    UndefinedVariable,
+    InvalidFunction,
+    InvalidMethod,
+    ArgumentCount,
+    InvalidArgument,
+    InvalidOperand,
+    InvalidIndex,
+    InvalidTableKey,
+    MetamethodLoop,
+    InvalidMetamethodResult,
+    StackOverflow,
+    InvalidForLoop,
+    CommandFailed,
+}
+
+/// The longer description of an error code, shown by `--explain`
+pub struct Explanation {
+    pub title: &'static str,
+    pub description: &'static str,
+    // Code making the mistake, and the same code fixed
+    pub example: &'static str,
+    pub fix: &'static str,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 30] = [
+        ErrorCode::UnexpectedCharacter,
+        ErrorCode::UnterminatedString,
+        ErrorCode::UnterminatedComment,
+        ErrorCode::UnclosedInterpolation,
+        ErrorCode::InvalidNumber,
+        ErrorCode::InvalidEscape,
+        ErrorCode::UnexpectedToken,
+        ErrorCode::UnexpectedEndOfFile,
+        ErrorCode::ReturnOutsideFunction,
+        ErrorCode::LoopControlOutsideLoop,
+        ErrorCode::InvalidAssignment,
+        ErrorCode::ExpectedStatement,
+        ErrorCode::DuplicateParameter,
+        ErrorCode::EmptyInterpolation,
+        ErrorCode::InvalidNumericFor,
+        ErrorCode::NestingTooDeep,
+        ErrorCode::InternalError,
+        ErrorCode::UndefinedVariable,
+        ErrorCode::InvalidFunction,
+        ErrorCode::InvalidMethod,
+        ErrorCode::ArgumentCount,
+        ErrorCode::InvalidArgument,
+        ErrorCode::InvalidOperand,
+        ErrorCode::InvalidIndex,
+        ErrorCode::InvalidTableKey,
+        ErrorCode::MetamethodLoop,
+        ErrorCode::InvalidMetamethodResult,
+        ErrorCode::StackOverflow,
+        ErrorCode::InvalidForLoop,
+        ErrorCode::CommandFailed,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => "M0001",
+            ErrorCode::UnterminatedString => "M0002",
+            ErrorCode::UnterminatedComment => "M0003",
+            ErrorCode::UnclosedInterpolation => "M0004",
+            ErrorCode::InvalidNumber => "M0005",
+            ErrorCode::InvalidEscape => "M0006",
+            ErrorCode::UnexpectedToken => "M0007",
+            ErrorCode::UnexpectedEndOfFile => "M0008",
+            ErrorCode::ReturnOutsideFunction => "M0009",
+            ErrorCode::LoopControlOutsideLoop => "M0010",
+            ErrorCode::InvalidAssignment => "M0011",
+            ErrorCode::ExpectedStatement => "M0012",
+            ErrorCode::DuplicateParameter => "M0013",
+            ErrorCode::EmptyInterpolation => "M0014",
+            ErrorCode::InvalidNumericFor => "M0015",
+            ErrorCode::NestingTooDeep => "M0016",
+
+            ErrorCode::InternalError => "M0100",
+            ErrorCode::UndefinedVariable => "M0101",
+            ErrorCode::InvalidFunction => "M0102",
+            ErrorCode::InvalidMethod => "M0103",
+            ErrorCode::ArgumentCount => "M0104",
+            ErrorCode::InvalidArgument => "M0105",
+            ErrorCode::InvalidOperand => "M0106",
+            ErrorCode::InvalidIndex => "M0107",
+            ErrorCode::InvalidTableKey => "M0108",
+            ErrorCode::MetamethodLoop => "M0109",
+            ErrorCode::InvalidMetamethodResult => "M0110",
+            ErrorCode::StackOverflow => "M0111",
+            ErrorCode::InvalidForLoop => "M0112",
+            ErrorCode::CommandFailed => "M0113",
+        }
+    }
+
+    /// Finds the error code for e.g. "M0102", ignoring case
+    pub fn from_code(code: &str) -> Option<Self> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|error_code| error_code.code().eq_ignore_ascii_case(code))
+    }
+
+    pub fn explanation(&self) -> Explanation {
+        match self {
+            ErrorCode::UnexpectedCharacter => Explanation {
+                title: "Unexpected character",
+                description: "The source code contains a character that is not part of the language, outside of a string or a comment.",
+                example: "let price = $5;",
+                fix: "let price = 5;",
+            },
+            ErrorCode::UnterminatedString => Explanation {
+                title: "Unterminated string",
+                description: "A string was opened with '\"' or a long bracket such as '[[', but never closed before the end of the file.",
+                example: "print(\"Hello);",
+                fix: "print(\"Hello\");",
+            },
+            ErrorCode::UnterminatedComment => Explanation {
+                title: "Unterminated block comment",
+                description: "A '/*' comment was never closed with '*/'. Block comments can be nested, so every '/*' inside of it needs its own '*/' too.",
+                example: "/* outer /* inner */\nprint(1);",
+                fix: "/* outer /* inner */ */\nprint(1);",
+            },
+            ErrorCode::UnclosedInterpolation => Explanation {
+                title: "Unclosed interpolation",
+                description: "An expression inside of a string was opened with '${', but never closed with '}'.",
+                example: "let total = \"Total: ${price * 2",
+                fix: "let total = \"Total: ${price * 2}\";",
+            },
+            ErrorCode::InvalidNumber => Explanation {
+                title: "Invalid number literal",
+                description: "A number literal is malformed, e.g. it has letters after its digits, an exponent without digits, a '0x' or '0b' prefix without digits, or it is too large.",
+                example: "let size = 12px;\nlet mask = 0x;",
+                fix: "let size = 12;\nlet mask = 0xFF;",
+            },
+            ErrorCode::InvalidEscape => Explanation {
+                title: "Invalid escape in string",
+                description: "A '\\' in a string is followed by something that is not a known escape. The escapes are \\n \\t \\r \\0 \\\\ \\\" \\$, \\x followed by two hex digits up to 7F, and \\u{...} with 1 to 6 hex digits. Long bracket strings such as [[C:\\path]] do not have escapes.",
+                example: "print(\"C:\\path\");",
+                fix: "print(\"C:\\\\path\");\nprint([[C:\\path]]);",
+            },
+            ErrorCode::UnexpectedToken => Explanation {
+                title: "Unexpected token",
+                description: "A token appears where the language does not allow it, e.g. a missing ';' or ')', or an operator without its operand.",
+                example: "let x = (1 + 2;\nprint(x)",
+                fix: "let x = (1 + 2);\nprint(x);",
+            },
+            ErrorCode::UnexpectedEndOfFile => Explanation {
+                title: "Unexpected end of file",
+                description: "The file ends in the middle of a statement, often because of a missing '}', ')' or ';'.",
+                example: "fn greet() {\n    print(\"Hi\");",
+                fix: "fn greet() {\n    print(\"Hi\");\n}",
+            },
+            ErrorCode::ReturnOutsideFunction => Explanation {
+                title: "'return' outside of a function",
+                description: "'return' can only be used inside of a function, there is nothing to return from at the top level of a file.",
+                example: "let x = 1;\nreturn x;",
+                fix: "fn get() {\n    let x = 1;\n    return x;\n}",
+            },
+            ErrorCode::LoopControlOutsideLoop => Explanation {
+                title: "'break' or 'continue' outside of a loop",
+                description: "'break' and 'continue' can only be used inside of a 'while' or 'for' loop. A function inside of a loop starts outside of any loop.",
+                example: "if done {\n    break;\n}",
+                fix: "while true {\n    if done {\n        break;\n    }\n}",
+            },
+            ErrorCode::InvalidAssignment => Explanation {
+                title: "Invalid assignment target",
+                description: "Only variables and table fields can be assigned to.",
+                example: "f() = 1;",
+                fix: "let result = f();\nresult = 1;",
+            },
+            ErrorCode::ExpectedStatement => Explanation {
+                title: "Expression used as a statement",
+                description: "An expression on its own does nothing, so a statement starting with an expression must be a function call or an assignment.",
+                example: "x + 1;",
+                fix: "x = x + 1;",
+            },
+            ErrorCode::DuplicateParameter => Explanation {
+                title: "Duplicate parameter",
+                description: "A function has two parameters with the same name, so the first one could never be used.",
+                example: "fn add(a, a) {\n    return a + a;\n}",
+                fix: "fn add(a, b) {\n    return a + b;\n}",
+            },
+            ErrorCode::EmptyInterpolation => Explanation {
+                title: "Empty interpolation",
+                description: "'${}' in a string must contain an expression. To write '${' itself, escape the '$' as '\\$'.",
+                example: "print(\"Cost: ${}\");",
+                fix: "print(\"Cost: ${cost}\");\nprint(\"Cost: \\${}\");",
+            },
+            ErrorCode::InvalidNumericFor => Explanation {
+                title: "Invalid numeric 'for'",
+                description: "A numeric 'for' needs a start and a stop, and optionally a step.",
+                example: "for i = 10 {\n    print(i);\n}",
+                fix: "for i = 1, 10 {\n    print(i);\n}",
+            },
+            ErrorCode::NestingTooDeep => Explanation {
+                title: "Nesting too deep",
+                description: "Expressions, such as parentheses, function calls and table constructors, and blocks are nested inside each other more than 200 levels deep. Split the code up with variables or functions.",
+                example: "",
+                fix: "",
+            },
+
+            ErrorCode::InternalError => Explanation {
+                title: "Internal error",
+                description: "The interpreter reached a state it does not support. This is a bug in the interpreter rather than in the program, please report it with the code that caused it.",
+                example: "",
+                fix: "",
+            },
+            ErrorCode::UndefinedVariable => Explanation {
+                title: "Undefined variable",
+                description: "A variable is used or assigned to before being declared with 'let', or it is not in scope, e.g. it was declared inside of another block.",
+                example: "count = 1;\nprint(total);",
+                fix: "let count = 1;\nlet total = count;\nprint(total);",
+            },
+            ErrorCode::InvalidFunction => Explanation {
+                title: "Invalid function",
+                description: "A value that is not a function is called, either a name that is not defined as a function, or a value of another type without a '__call' metamethod.",
+                example: "let greet = \"Hi\";\ngreet();",
+                fix: "fn greet() {\n    print(\"Hi\");\n}\ngreet();",
+            },
+            ErrorCode::InvalidMethod => Explanation {
+                title: "Invalid method",
+                description: "'value:method()' calls the function found at 'value.method', but there is no such function. Strings have the methods of the 'string' library, tables can get methods from their own fields or from an '__index' metamethod.",
+                example: "let name = \"minute\";\nprint(name:upper_case());",
+                fix: "let name = \"minute\";\nprint(name:upper());",
+            },
+            ErrorCode::ArgumentCount => Explanation {
+                title: "Wrong number of arguments",
+                description: "A function is called with more or fewer arguments than it has parameters.",
+                example: "fn add(a, b) {\n    return a + b;\n}\nprint(add(1));",
+                fix: "fn add(a, b) {\n    return a + b;\n}\nprint(add(1, 2));",
+            },
+            ErrorCode::InvalidArgument => Explanation {
+                title: "Invalid argument",
+                description: "A builtin function is given an argument of a type it does not accept, e.g. a number where it expects a table.",
+                example: "setmetatable(1, {});",
+                fix: "setmetatable({}, {});",
+            },
+            ErrorCode::InvalidOperand => Explanation {
+                title: "Invalid operand",
+                description: "An operator is used with values of types it does not support, e.g. adding a string to a number. Tables can support operators with metamethods such as '__add'.",
+                example: "print(\"Total: \" + 5);",
+                fix: "print(\"Total: ${5}\");",
+            },
+            ErrorCode::InvalidIndex => Explanation {
+                title: "Invalid index",
+                description: "A value that is not a table, and has no '__index' metamethod, is indexed with '.' or '[]'.",
+                example: "let count = 5;\nprint(count.value);",
+                fix: "let counter = { value = 5 };\nprint(counter.value);",
+            },
+            ErrorCode::InvalidTableKey => Explanation {
+                title: "Invalid table key",
+                description: "Tables cannot have nil or NaN as a key, since neither is equal to anything that could look it up again.",
+                example: "let t = {};\nt[nil] = 1;",
+                fix: "let t = {};\nt[\"none\"] = 1;",
+            },
+            ErrorCode::MetamethodLoop => Explanation {
+                title: "Metamethod loop",
+                description: "Following the '__index', '__newindex' or '__call' metamethods went through too many values, which usually means the tables point at each other.",
+                example: "let a = {};\nsetmetatable(a, { __index = a });\nprint(a.x);",
+                fix: "let a = {};\nsetmetatable(a, { __index = { x = 1 } });\nprint(a.x);",
+            },
+            ErrorCode::InvalidMetamethodResult => Explanation {
+                title: "Invalid metamethod result",
+                description: "A metamethod returned a value of the wrong type, e.g. '__tostring' must return a string.",
+                example: "let t = setmetatable({}, { __tostring = fn(self) { return 1; } });\nprint(t);",
+                fix: "let t = setmetatable({}, { __tostring = fn(self) { return \"t\"; } });\nprint(t);",
+            },
+            ErrorCode::StackOverflow => Explanation {
+                title: "Stack overflow",
+                description: "Too many function calls are nested, usually because a recursive function never reaches the case where it stops calling itself.",
+                example: "fn count(n) {\n    return count(n + 1);\n}\ncount(1);",
+                fix: "fn count(n) {\n    if n >= 10 {\n        return n;\n    }\n    return count(n + 1);\n}\ncount(1);",
+            },
+            ErrorCode::InvalidForLoop => Explanation {
+                title: "Invalid 'for' loop",
+                description: "The start, stop and step of a numeric 'for' must be numbers and the step cannot be zero, and a generic 'for' must be given an iterator function such as 'pairs(t)'.",
+                example: "for i = 1, 10, 0 { }\nfor key, value in t { }",
+                fix: "for i = 1, 10, 1 { }\nfor key, value in pairs(t) { }",
+            },
+            ErrorCode::CommandFailed => Explanation {
+                title: "Command failed",
+                description: "'syscall' could not run the command, e.g. because it is not installed or not in the PATH.",
+                example: "syscall(\"not-a-command\");",
+                fix: "syscall(\"echo\", \"hello\");",
+            },
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
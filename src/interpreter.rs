@@ -1,128 +1,1505 @@
-use std::io::{self, Write};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
 
 use crate::{
-    ast::{Node, NodeKind},
-    tokens::{Location},
+    ast::{BinaryOperator, Node, NodeKind, UnaryOperator},
+    diagnostic::Diagnostic,
+    environment::Environment,
+    error_code::ErrorCode,
+    suggestion::similar_name,
+    table::Table,
+    tokens::Location,
+    value::{Function, UserFunction, Value},
 };
 
+/// How execution continues after a statement
+#[derive(Debug, Clone, PartialEq)]
+enum ControlFlow {
+    Normal,
+    Break,
+    Continue,
+    Return(Vec<Value>),
+}
+
+/// A function call in progress
+#[derive(Clone)]
+struct CallFrame {
+    function_name: String,
+    location: Location,
+}
+
+// Deeper recursion would overflow the stack `main` runs the interpreter on, together with the
+// parser's limit on how deeply code is nested
+const MAX_CALL_DEPTH: usize = 200;
+const MAX_SHOWN_CALL_FRAMES: usize = 10;
+
+const BUILTINS: [&str; 11] = [
+    "print",
+    "tostring",
+    "type",
+    "syscall",
+    "next",
+    "pairs",
+    "ipairs",
+    "setmetatable",
+    "getmetatable",
+    "rawget",
+    "rawset",
+];
+
+// Functions of the `string` table, which is also the `__index` of every string
+const STRING_LIBRARY: [(&str, &str); 5] = [
+    ("len", "string.len"),
+    ("upper", "string.upper"),
+    ("lower", "string.lower"),
+    ("sub", "string.sub"),
+    ("rep", "string.rep"),
+];
+
+// Longest string `string.rep` can create, in bytes
+const MAX_STRING_LENGTH: usize = 1 << 28;
+
+// How many `__index`, `__newindex` or `__call` values are followed before giving up
+const MAX_METATABLE_CHAIN: usize = 100;
+
 #[derive(Clone)]
 pub struct Interpreter {
     input_nodes: Vec<Node>,
+    // The innermost scope of the code being run
+    environment: Rc<RefCell<Environment>>,
+    call_stack: Vec<CallFrame>,
+    // Shared by all strings, so that `s:upper()` works
+    string_metatable: Rc<RefCell<Table>>,
 }
 
 impl Interpreter {
     pub fn new(input_nodes: Vec<Node>) -> Self {
-        Self { input_nodes }
+        let globals = Environment::new(None);
+        for builtin in BUILTINS {
+            globals
+                .borrow_mut()
+                .declare(builtin, Value::Function(Function::Builtin(builtin)));
+        }
+
+        let mut string_library = Table::default();
+        for (key, builtin) in STRING_LIBRARY {
+            string_library.set(
+                Value::String(String::from(key)),
+                Value::Function(Function::Builtin(builtin)),
+            );
+        }
+        let string_library = Rc::new(RefCell::new(string_library));
+
+        let mut string_metatable = Table::default();
+        string_metatable.set(
+            Value::String(String::from("__index")),
+            Value::Table(string_library.clone()),
+        );
+
+        globals
+            .borrow_mut()
+            .declare("string", Value::Table(string_library));
+
+        Self {
+            input_nodes,
+            environment: globals,
+            call_stack: Vec::new(),
+            string_metatable: Rc::new(RefCell::new(string_metatable)),
+        }
     }
 
-    pub fn interpret(&mut self) {
+    pub fn interpret(&mut self) -> Result<(), Box<Diagnostic>> {
         for node in self.clone().input_nodes.iter() {
-            self.match_node(node.clone());
+            self.match_node(node)?;
         }
+
+        Ok(())
     }
 
-    fn match_node(&mut self, node: Node) {
-        let node = node.clone();
-        let node_kind = node.kind.clone();
+    /// Reads a variable of the script, e.g. to use its results after `interpret`
+    pub fn get_variable(&self, variable_name: &str) -> Option<Value> {
+        self.environment.borrow().get(variable_name)
+    }
+
+    fn match_node(&mut self, node: &Node) -> Result<ControlFlow, Box<Diagnostic>> {
         let node_location = node.location.clone();
 
-        match node_kind {
-            NodeKind::FunctionCall(function_name, arguments) => match function_name.as_str() {
-                "print" => {
-                    for argument in arguments.iter() {
-                        match &argument.kind {
-                            NodeKind::String(argument) => {
-                                let mut stdout = io::stdout();
-                                stdout.write(argument.as_bytes()).and(stdout.flush()).ok();
-                            }
+        match &node.kind {
+            // The result of a function call statement is discarded
+            NodeKind::FunctionCall(..) | NodeKind::MethodCall(..) => {
+                self.evaluate(node)?;
+            }
 
-                            _ => {
-                                self.throw_err(format!(
-                                        "Invalid argument of kind '{:?}' for function '{}', expected of kind 'String'",
-                                        argument.kind, function_name
-                                    ), node_location.clone());
-                            }
-                        }
+            NodeKind::Let(variable_name, value) => {
+                let value = self.evaluate(value)?;
+                self.environment
+                    .borrow_mut()
+                    .declare(variable_name.clone(), value);
+            }
+
+            NodeKind::Assign(target, value) => match &target.kind {
+                NodeKind::Identifier(variable_name) => {
+                    let value = self.evaluate(value)?;
+
+                    if !self.environment.borrow_mut().assign(variable_name, value) {
+                        let mut diagnostic = self.error(
+                            ErrorCode::UndefinedVariable,
+                            format!(
+                                "Cannot assign to undefined variable '{}', declare it with 'let' first",
+                                variable_name
+                            ),
+                            node_location,
+                        );
+                        diagnostic
+                            .suggestions
+                            .extend(self.similar_variable(variable_name, false));
+                        return Err(diagnostic);
+                    }
+                }
+
+                NodeKind::Index(table, key) => {
+                    let table = self.evaluate(table)?;
+                    let key = self.evaluate(key)?;
+                    let value = self.evaluate(value)?;
+
+                    self.set_index(table, key, value, node_location)?;
+                }
+
+                _ => unreachable!("Assignment to '{:?}'", target.kind),
+            },
+
+            NodeKind::Block(statements) => {
+                let environment = Environment::new(Some(self.environment.clone()));
+                return self.execute_block(statements, environment);
+            }
+
+            NodeKind::If(branches, else_block) => {
+                for (condition, block) in branches.iter() {
+                    if self.evaluate(condition)?.is_truthy() {
+                        return self.match_node(block);
+                    }
+                }
+
+                if let Some(else_block) = else_block {
+                    return self.match_node(else_block);
+                }
+            }
+
+            NodeKind::While(condition, block) => {
+                while self.evaluate(condition)?.is_truthy() {
+                    match self.match_node(block)? {
+                        ControlFlow::Break => break,
+                        ControlFlow::Return(values) => return Ok(ControlFlow::Return(values)),
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                    }
+                }
+            }
+
+            NodeKind::NumericFor(variable_name, start, stop, step, block) => {
+                let start = self.evaluate_for_number("initial", start)?;
+                let stop = self.evaluate_for_number("limit", stop)?;
+                let step = match step {
+                    Some(step) => self.evaluate_for_number("step", step)?,
+                    None => 1.0,
+                };
+
+                if step == 0.0 {
+                    return Err(self.error(
+                        ErrorCode::InvalidForLoop,
+                        "'for' step is zero",
+                        node_location,
+                    ));
+                }
+
+                let mut counter = start;
+                while (step > 0.0 && counter <= stop) || (step < 0.0 && counter >= stop) {
+                    match self.run_loop_iteration(
+                        std::slice::from_ref(variable_name),
+                        vec![Value::Number(counter)],
+                        block,
+                    )? {
+                        ControlFlow::Break => break,
+                        ControlFlow::Return(values) => return Ok(ControlFlow::Return(values)),
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                    }
+
+                    counter += step;
+                }
+            }
+
+            // Follows Lua's iterator protocol, `iterator(state, control)` is called before every
+            // iteration until its first return value is nil
+            NodeKind::GenericFor(variable_names, iterator, block) => {
+                let mut values = self.evaluate_list(iterator)?.into_iter();
+
+                let iterator = values.next().unwrap_or(Value::Nil);
+                if !matches!(iterator, Value::Function(_))
+                    && self.metamethod(&iterator, "__call").is_none()
+                {
+                    return Err(self.error(
+                        ErrorCode::InvalidForLoop,
+                        format!(
+                            "'for' iterator must be a function, but is of type '{}'",
+                            iterator.type_name()
+                        ),
+                        node_location,
+                    ));
+                }
+                let state = values.next().unwrap_or(Value::Nil);
+                let mut control = values.next().unwrap_or(Value::Nil);
+
+                loop {
+                    let results = self.call_value(
+                        iterator.clone(),
+                        vec![state.clone(), control.clone()],
+                        node_location.clone(),
+                    )?;
+
+                    control = results.first().cloned().unwrap_or(Value::Nil);
+                    if control == Value::Nil {
+                        break;
+                    }
+
+                    match self.run_loop_iteration(variable_names, results, block)? {
+                        ControlFlow::Break => break,
+                        ControlFlow::Return(values) => return Ok(ControlFlow::Return(values)),
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                    }
+                }
+            }
+
+            NodeKind::FunctionDefinition(function_name, parameters, block) => {
+                // Declared before creating the function, so that it sees itself rather than an
+                // older variable with the same name
+                self.environment
+                    .borrow_mut()
+                    .declare(function_name.clone(), Value::Nil);
+                let function =
+                    self.create_function(function_name, parameters, block, node_location);
+
+                self.environment
+                    .borrow_mut()
+                    .assign(function_name, function);
+            }
+
+            NodeKind::Return(values) => {
+                return Ok(ControlFlow::Return(self.evaluate_list(values)?))
+            }
+
+            NodeKind::Break => return Ok(ControlFlow::Break),
+            NodeKind::Continue => return Ok(ControlFlow::Continue),
+
+            other => {
+                return Err(self.error(
+                    ErrorCode::InternalError,
+                    format!("Unimplemented node '{:?}'", other),
+                    node_location,
+                ));
+            }
+        }
+
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Evaluates an expression to a runtime value
+    fn evaluate(&mut self, node: &Node) -> Result<Value, Box<Diagnostic>> {
+        let node_location = node.location.clone();
+
+        Ok(match &node.kind {
+            NodeKind::Nil => Value::Nil,
+            NodeKind::Boolean(boolean) => Value::Boolean(*boolean),
+            NodeKind::Number(number) => Value::Number(*number),
+            NodeKind::String(string) => Value::String(string.clone()),
+
+            NodeKind::Interpolation(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    let value = self.evaluate(part)?;
+                    string.push_str(&self.tostring(&value, part.location.clone())?);
+                }
+
+                Value::String(string)
+            }
+
+            NodeKind::Identifier(variable_name) => {
+                match self.environment.borrow().get(variable_name) {
+                    Some(value) => value,
+                    None => {
+                        let mut diagnostic = self.error(
+                            ErrorCode::UndefinedVariable,
+                            format!("Undefined variable '{}'", variable_name),
+                            node_location,
+                        );
+                        diagnostic
+                            .suggestions
+                            .extend(self.similar_variable(variable_name, false));
+                        return Err(diagnostic);
                     }
                 }
+            }
 
-                "syscall" => {
-                    if arguments.len() < 1 {
-                        self.throw_err(format!(
-                            "Insufficient amount of arguments for function '{}', at least 1 required.\n[Help]\n{}",
-                            function_name,
-                            "([command_name], [arguments]...)\nAll the arguments are strings.",
-                        ), node_location.clone());
+            NodeKind::UnaryOp(operator, operand) => {
+                let operand = self.evaluate(operand)?;
+
+                match (operator, operand) {
+                    (UnaryOperator::Negate, Value::Number(number)) => Value::Number(-number),
+                    (UnaryOperator::Not, operand) => Value::Boolean(!operand.is_truthy()),
+                    (UnaryOperator::Length, Value::String(string)) => {
+                        Value::Number(string.chars().count() as f64)
                     }
 
-                    let mut command_list = Vec::<String>::new();
+                    (operator, operand)
+                        if self.metamethod(&operand, unary_event(*operator)).is_some() =>
+                    {
+                        let metamethod = self.metamethod(&operand, unary_event(*operator)).unwrap();
+                        self.call_metamethod(metamethod, vec![operand], node_location)?
+                    }
 
-                    for argument in arguments.iter() {
-                        match &argument.kind {
-                            NodeKind::String(argument) => {
-                                command_list.push(argument.to_string());
+                    (UnaryOperator::Length, Value::Table(table)) => {
+                        Value::Number(table.borrow().length() as f64)
+                    }
+
+                    (operator, other) => {
+                        return Err(self.error(
+                            ErrorCode::InvalidOperand,
+                            format!(
+                                "Invalid operand of type '{}' for operator '{}'",
+                                other.type_name(),
+                                operator
+                            ),
+                            node_location,
+                        ))
+                    }
+                }
+            }
+
+            // Like Lua, `and` and `or` short-circuit and return one of their operands
+            NodeKind::BinaryOp(BinaryOperator::And, left, right) => {
+                let left = self.evaluate(left)?;
+                if left.is_truthy() {
+                    self.evaluate(right)?
+                } else {
+                    left
+                }
+            }
+
+            NodeKind::BinaryOp(BinaryOperator::Or, left, right) => {
+                let left = self.evaluate(left)?;
+                if left.is_truthy() {
+                    left
+                } else {
+                    self.evaluate(right)?
+                }
+            }
+
+            NodeKind::BinaryOp(operator, left, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+
+                self.evaluate_binary_op(*operator, left, right, node_location)?
+            }
+
+            NodeKind::Table(entries) => {
+                let table = Rc::new(RefCell::new(Table::default()));
+                let mut next_position = 1;
+
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    match key {
+                        Some(key) => {
+                            let key = self.evaluate(key)?;
+                            self.check_table_key(&key, node_location.clone())?;
+
+                            let value = self.evaluate(value)?;
+                            table.borrow_mut().set(key, value);
+                        }
+
+                        // Like Lua, a function call as the last positional entry expands to
+                        // all of its values
+                        None => {
+                            let values = if index == entries.len() - 1 {
+                                self.evaluate_multiple(value)?
+                            } else {
+                                vec![self.evaluate(value)?]
+                            };
+
+                            for value in values {
+                                table
+                                    .borrow_mut()
+                                    .set(Value::Number(next_position as f64), value);
+                                next_position += 1;
                             }
+                        }
+                    }
+                }
+
+                Value::Table(table)
+            }
+
+            NodeKind::Index(table, key) => {
+                let table = self.evaluate(table)?;
+                let key = self.evaluate(key)?;
 
-                            _ => {
-                                self.throw_err(format!(
-                                        "Invalid argument of kind '{:?}' for function '{}', expected of kind 'String'",
-                                        argument.kind, function_name
-                                    ), node_location.clone());
+                self.index(table, key, node_location)?
+            }
+
+            NodeKind::Function(parameters, block) => {
+                self.create_function("anonymous", parameters, block, node_location)
+            }
+
+            // Only the first returned value is used, or nil if there is none
+            NodeKind::FunctionCall(..) | NodeKind::MethodCall(..) => self
+                .evaluate_multiple(node)?
+                .into_iter()
+                .next()
+                .unwrap_or(Value::Nil),
+
+            other => {
+                return Err(self.error(
+                    ErrorCode::InternalError,
+                    format!("Unimplemented node '{:?}'", other),
+                    node_location,
+                ));
+            }
+        })
+    }
+
+    fn evaluate_for_number(&mut self, name: &str, node: &Node) -> Result<f64, Box<Diagnostic>> {
+        Ok(match self.evaluate(node)? {
+            Value::Number(number) => number,
+            other => {
+                return Err(self.error(
+                    ErrorCode::InvalidForLoop,
+                    format!(
+                        "'for' {} value must be a number, but is of type '{}'",
+                        name,
+                        other.type_name()
+                    ),
+                    node.location.clone(),
+                ))
+            }
+        })
+    }
+
+    /// Runs a loop's block in a new scope holding the loop variables, missing values are nil
+    fn run_loop_iteration(
+        &mut self,
+        variable_names: &[String],
+        values: Vec<Value>,
+        block: &Node,
+    ) -> Result<ControlFlow, Box<Diagnostic>> {
+        let mut values = values.into_iter();
+        let environment = Environment::new(Some(self.environment.clone()));
+        for variable_name in variable_names {
+            environment
+                .borrow_mut()
+                .declare(variable_name.clone(), values.next().unwrap_or(Value::Nil));
+        }
+
+        self.execute_block(std::slice::from_ref(block), environment)
+    }
+
+    /// Runs `statements` in `environment`, then switches back to the current environment
+    fn execute_block(
+        &mut self,
+        statements: &[Node],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<ControlFlow, Box<Diagnostic>> {
+        let previous_environment = std::mem::replace(&mut self.environment, environment);
+
+        // The environment is switched back even if a statement fails
+        let mut control_flow = Ok(ControlFlow::Normal);
+        for statement in statements.iter() {
+            control_flow = self.match_node(statement);
+            if !matches!(control_flow, Ok(ControlFlow::Normal)) {
+                break;
+            }
+        }
+
+        self.environment = previous_environment;
+
+        control_flow
+    }
+
+    /// Creates a closure capturing the current environment
+    fn create_function(
+        &self,
+        function_name: &str,
+        parameters: &[String],
+        block: &Rc<Node>,
+        node_location: Location,
+    ) -> Value {
+        Value::Function(Function::User(
+            Rc::new(UserFunction {
+                name: String::from(function_name),
+                parameters: parameters.to_vec(),
+                block: block.clone(),
+                closure: Rc::downgrade(&self.environment),
+                declarations: self.environment.borrow().declarations(),
+                location: node_location,
+            }),
+            Some(self.environment.clone()),
+        ))
+    }
+
+    /// Evaluates an expression which may produce multiple values, i.e. a function call
+    fn evaluate_multiple(&mut self, node: &Node) -> Result<Vec<Value>, Box<Diagnostic>> {
+        let node_location = node.location.clone();
+
+        Ok(match &node.kind {
+            NodeKind::FunctionCall(function, arguments) => {
+                let function = match &function.kind {
+                    NodeKind::Identifier(function_name) => {
+                        let function = self.environment.borrow().get(function_name);
+                        match function {
+                            Some(function) => function,
+
+                            None => {
+                                let mut diagnostic = self.error(
+                                    ErrorCode::InvalidFunction,
+                                    format!("Invalid function '{}'", function_name),
+                                    node_location,
+                                );
+                                diagnostic
+                                    .suggestions
+                                    .extend(self.similar_variable(function_name, true));
+                                return Err(diagnostic);
                             }
                         }
                     }
 
-                    let mut process_command = std::process::Command::new(command_list[0].clone());
-                    for command in command_list.iter().skip(1) {
-                        process_command.arg(command);
+                    _ => self.evaluate(function)?,
+                };
+
+                let arguments = self.evaluate_list(arguments)?;
+
+                self.call_value(function, arguments, node_location)?
+            }
+
+            // object:method(arguments) is object.method(object, arguments), with object
+            // only evaluated once
+            NodeKind::MethodCall(object, method_name, arguments) => {
+                let object = self.evaluate(object)?;
+                let method = self.index(
+                    object.clone(),
+                    Value::String(method_name.clone()),
+                    node_location.clone(),
+                )?;
+
+                if method == Value::Nil {
+                    return Err(self.error(
+                        ErrorCode::InvalidMethod,
+                        format!(
+                            "Invalid method '{}' for a value of type '{}'",
+                            method_name,
+                            object.type_name()
+                        ),
+                        node_location,
+                    ));
+                }
+
+                let mut method_arguments = vec![object];
+                method_arguments.extend(self.evaluate_list(arguments)?);
+
+                self.call_value(method, method_arguments, node_location)?
+            }
+
+            _ => vec![self.evaluate(node)?],
+        })
+    }
+
+    /// Evaluates a list of expressions like Lua does, only the last one expands to all of its values
+    fn evaluate_list(&mut self, nodes: &[Node]) -> Result<Vec<Value>, Box<Diagnostic>> {
+        let mut values = Vec::new();
+
+        for (index, node) in nodes.iter().enumerate() {
+            if index == nodes.len() - 1 {
+                values.extend(self.evaluate_multiple(node)?);
+            } else {
+                values.push(self.evaluate(node)?);
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Indexes `table[key]`, following `__index` metamethods for missing keys
+    fn index(
+        &mut self,
+        table: Value,
+        key: Value,
+        node_location: Location,
+    ) -> Result<Value, Box<Diagnostic>> {
+        let mut current = table;
+
+        for _ in 0..MAX_METATABLE_CHAIN {
+            if let Value::Table(table) = &current {
+                let value = table.borrow().get(&key);
+                if value != Value::Nil {
+                    return Ok(value);
+                }
+            }
+
+            let handler = match (self.metamethod(&current, "__index"), &current) {
+                (Some(handler), _) => handler,
+                (None, Value::Table(_)) => return Ok(Value::Nil),
+
+                (None, other) => {
+                    return Err(self.error(
+                        ErrorCode::InvalidIndex,
+                        format!(
+                            "Cannot index a value of type '{}' with key '{}'",
+                            other.type_name(),
+                            key
+                        ),
+                        node_location,
+                    ))
+                }
+            };
+
+            if let Value::Table(_) = handler {
+                current = handler;
+                continue;
+            }
+
+            return self.call_metamethod(handler, vec![current, key], node_location);
+        }
+
+        Err(self.error(
+            ErrorCode::MetamethodLoop,
+            "'__index' chain is too long, it may be a loop",
+            node_location,
+        ))
+    }
+
+    /// Assigns `table[key] = value`, following `__newindex` metamethods for missing keys
+    fn set_index(
+        &mut self,
+        table: Value,
+        key: Value,
+        value: Value,
+        node_location: Location,
+    ) -> Result<(), Box<Diagnostic>> {
+        let mut current = table;
+
+        for _ in 0..MAX_METATABLE_CHAIN {
+            let handler = self.metamethod(&current, "__newindex");
+
+            match (&current, handler) {
+                (Value::Table(table), handler)
+                    if handler.is_none() || table.borrow().get(&key) != Value::Nil =>
+                {
+                    self.check_table_key(&key, node_location)?;
+                    table.borrow_mut().set(key, value);
+                    return Ok(());
+                }
+
+                (_, Some(Value::Table(handler))) => current = Value::Table(handler),
+
+                (_, Some(handler)) => {
+                    self.call_value(handler, vec![current, key, value], node_location)?;
+                    return Ok(());
+                }
+
+                (other, None) => {
+                    return Err(self.error(
+                        ErrorCode::InvalidIndex,
+                        format!(
+                            "Cannot index a value of type '{}' with key '{}'",
+                            other.type_name(),
+                            key
+                        ),
+                        node_location,
+                    ))
+                }
+            }
+        }
+
+        Err(self.error(
+            ErrorCode::MetamethodLoop,
+            "'__newindex' chain is too long, it may be a loop",
+            node_location,
+        ))
+    }
+
+    /// Returns the metamethod `event` from the metatable of `value`, if it has one
+    fn metamethod(&self, value: &Value, event: &str) -> Option<Value> {
+        let metatable = match value {
+            Value::Table(table) => table.borrow().metatable.clone()?,
+            Value::String(_) => self.string_metatable.clone(),
+            _ => return None,
+        };
+
+        let metamethod = metatable.borrow().get(&Value::String(String::from(event)));
+
+        match metamethod {
+            Value::Nil => None,
+            metamethod => Some(metamethod),
+        }
+    }
+
+    /// Calls a metamethod, only using its first return value
+    fn call_metamethod(
+        &mut self,
+        metamethod: Value,
+        arguments: Vec<Value>,
+        node_location: Location,
+    ) -> Result<Value, Box<Diagnostic>> {
+        Ok(self
+            .call_value(metamethod, arguments, node_location)?
+            .into_iter()
+            .next()
+            .unwrap_or(Value::Nil))
+    }
+
+    /// Converts a value to a string, using its `__tostring` metamethod if it has one
+    fn tostring(
+        &mut self,
+        value: &Value,
+        node_location: Location,
+    ) -> Result<String, Box<Diagnostic>> {
+        match self.metamethod(value, "__tostring") {
+            Some(metamethod) => {
+                match self.call_metamethod(
+                    metamethod,
+                    vec![value.clone()],
+                    node_location.clone(),
+                )? {
+                    Value::String(string) => Ok(string),
+                    other => Err(self.error(
+                        ErrorCode::InvalidMetamethodResult,
+                        format!(
+                            "'__tostring' must return a string, but returned a value of type '{}'",
+                            other.type_name()
+                        ),
+                        node_location,
+                    )),
+                }
+            }
+
+            None => Ok(value.to_string()),
+        }
+    }
+
+    fn check_table_key(&self, key: &Value, node_location: Location) -> Result<(), Box<Diagnostic>> {
+        match key {
+            Value::Nil => Err(self.error(
+                ErrorCode::InvalidTableKey,
+                "Table key cannot be nil",
+                node_location,
+            )),
+            Value::Number(number) if number.is_nan() => Err(self.error(
+                ErrorCode::InvalidTableKey,
+                "Table key cannot be NaN",
+                node_location,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn evaluate_binary_op(
+        &mut self,
+        operator: BinaryOperator,
+        left: Value,
+        right: Value,
+        node_location: Location,
+    ) -> Result<Value, Box<Diagnostic>> {
+        Ok(match (operator, left, right) {
+            (operator @ (BinaryOperator::Equal | BinaryOperator::NotEqual), left, right) => {
+                let mut equal = left == right;
+
+                // `__eq` is only consulted for two different tables
+                if !equal && matches!((&left, &right), (Value::Table(_), Value::Table(_))) {
+                    if let Some(metamethod) = self
+                        .metamethod(&left, "__eq")
+                        .or_else(|| self.metamethod(&right, "__eq"))
+                    {
+                        equal = self
+                            .call_metamethod(metamethod, vec![left, right], node_location)?
+                            .is_truthy();
+                    }
+                }
+
+                Value::Boolean(if operator == BinaryOperator::Equal {
+                    equal
+                } else {
+                    !equal
+                })
+            }
+
+            (operator, Value::Number(left), Value::Number(right)) => match operator {
+                BinaryOperator::Add => Value::Number(left + right),
+                BinaryOperator::Subtract => Value::Number(left - right),
+                BinaryOperator::Multiply => Value::Number(left * right),
+                BinaryOperator::Divide => Value::Number(left / right),
+                // Like Lua, the result takes the sign of the divisor
+                BinaryOperator::Modulo => Value::Number(left - (left / right).floor() * right),
+
+                BinaryOperator::Less => Value::Boolean(left < right),
+                BinaryOperator::LessEqual => Value::Boolean(left <= right),
+                BinaryOperator::Greater => Value::Boolean(left > right),
+                BinaryOperator::GreaterEqual => Value::Boolean(left >= right),
+
+                BinaryOperator::And
+                | BinaryOperator::Or
+                | BinaryOperator::Equal
+                | BinaryOperator::NotEqual => unreachable!(),
+            },
+
+            (
+                operator @ (BinaryOperator::Less
+                | BinaryOperator::LessEqual
+                | BinaryOperator::Greater
+                | BinaryOperator::GreaterEqual),
+                Value::String(left),
+                Value::String(right),
+            ) => Value::Boolean(match operator {
+                BinaryOperator::Less => left < right,
+                BinaryOperator::LessEqual => left <= right,
+                BinaryOperator::Greater => left > right,
+                _ => left >= right,
+            }),
+
+            // a > b is b < a and a >= b is b <= a
+            (operator @ (BinaryOperator::Greater | BinaryOperator::GreaterEqual), left, right)
+                if self.metamethod(&left, binary_event(operator)).is_some()
+                    || self.metamethod(&right, binary_event(operator)).is_some() =>
+            {
+                let metamethod = self
+                    .metamethod(&right, binary_event(operator))
+                    .or_else(|| self.metamethod(&left, binary_event(operator)))
+                    .unwrap();
+
+                Value::Boolean(
+                    self.call_metamethod(metamethod, vec![right, left], node_location)?
+                        .is_truthy(),
+                )
+            }
+
+            (operator, left, right)
+                if self.metamethod(&left, binary_event(operator)).is_some()
+                    || self.metamethod(&right, binary_event(operator)).is_some() =>
+            {
+                let metamethod = self
+                    .metamethod(&left, binary_event(operator))
+                    .or_else(|| self.metamethod(&right, binary_event(operator)))
+                    .unwrap();
+
+                let result = self.call_metamethod(metamethod, vec![left, right], node_location)?;
+
+                match operator {
+                    BinaryOperator::Less | BinaryOperator::LessEqual => {
+                        Value::Boolean(result.is_truthy())
                     }
+                    _ => result,
+                }
+            }
 
-                    match process_command.output() {
-                        Ok(ok) => {
-                            let mut stdout = io::stdout();
-                            stdout.write(&ok.stdout).and(stdout.flush()).ok();
+            (operator, left, right) => {
+                return Err(self.error(
+                    ErrorCode::InvalidOperand,
+                    format!(
+                        "Invalid operands of types '{}' and '{}' for operator '{}'",
+                        left.type_name(),
+                        right.type_name(),
+                        operator
+                    ),
+                    node_location,
+                ))
+            }
+        })
+    }
 
-                            if !ok.stderr.is_empty() {
-                                stdout.write(&ok.stderr).and(stdout.flush()).ok();
-                            }
+    /// Calls a function, or a value with a `__call` metamethod which gets the value as its
+    /// first argument
+    fn call_value(
+        &mut self,
+        function: Value,
+        mut arguments: Vec<Value>,
+        node_location: Location,
+    ) -> Result<Vec<Value>, Box<Diagnostic>> {
+        let mut current = function;
+
+        // A `__call` can be another value with a `__call`, each one is passed to the next
+        for _ in 0..MAX_METATABLE_CHAIN {
+            match current {
+                Value::Function(function) => {
+                    return self.call_function(function, arguments, node_location)
+                }
+
+                other => match self.metamethod(&other, "__call") {
+                    Some(metamethod) => {
+                        arguments.insert(0, other);
+                        current = metamethod;
+                    }
+
+                    None => {
+                        return Err(self.error(
+                            ErrorCode::InvalidFunction,
+                            format!("Cannot call a value of type '{}'", other.type_name()),
+                            node_location,
+                        ))
+                    }
+                },
+            }
+        }
+
+        Err(self.error(
+            ErrorCode::MetamethodLoop,
+            "'__call' chain is too long, it may be a loop",
+            node_location,
+        ))
+    }
+
+    fn call_function(
+        &mut self,
+        function: Function,
+        arguments: Vec<Value>,
+        node_location: Location,
+    ) -> Result<Vec<Value>, Box<Diagnostic>> {
+        match function {
+            Function::Builtin(name) => self.call_builtin(name, arguments, node_location),
+
+            Function::User(function, closure) => {
+                self.expect_argument_count(
+                    &function.name,
+                    &arguments,
+                    function.parameters.len(),
+                    node_location.clone(),
+                )
+                .map_err(|diagnostic| {
+                    // Only the first line of the definition, rather than its whole body
+                    let mut definition_location = function.location.clone();
+                    if definition_location.end_line != definition_location.start_line {
+                        definition_location.end_line = definition_location.start_line;
+                        definition_location.end_col = definition_location.line.chars().count();
+                    }
+
+                    diagnostic.with_label(definition_location, "function defined here")
+                })?;
+
+                if self.call_stack.len() >= MAX_CALL_DEPTH {
+                    return Err(self.error(
+                        ErrorCode::StackOverflow,
+                        format!(
+                            "Stack overflow, exceeded {} nested calls while calling '{}'",
+                            MAX_CALL_DEPTH, function.name
+                        ),
+                        node_location,
+                    ));
+                }
+
+                self.call_stack.push(CallFrame {
+                    function_name: function.name.clone(),
+                    location: node_location,
+                });
+
+                // The function sees the variables of where it was created, not of the caller
+                let closure = closure.or_else(|| function.closure.upgrade());
+                let environment = Environment::new_at(closure, function.declarations);
+                for (parameter, argument) in function.parameters.iter().zip(arguments) {
+                    environment
+                        .borrow_mut()
+                        .declare(parameter.clone(), argument);
+                }
+
+                let control_flow =
+                    self.execute_block(std::slice::from_ref(&*function.block), environment);
+
+                self.call_stack.pop();
+
+                match control_flow? {
+                    ControlFlow::Return(values) => Ok(values),
+                    _ => Ok(Vec::new()),
+                }
+            }
+        }
+    }
+
+    fn call_builtin(
+        &mut self,
+        function_name: &str,
+        arguments: Vec<Value>,
+        node_location: Location,
+    ) -> Result<Vec<Value>, Box<Diagnostic>> {
+        Ok(match function_name {
+            "print" => {
+                for argument in arguments.iter() {
+                    let argument = self.tostring(argument, node_location.clone())?;
+
+                    let mut stdout = io::stdout();
+                    stdout.write(argument.as_bytes()).and(stdout.flush()).ok();
+                }
+
+                Vec::new()
+            }
+
+            "tostring" => {
+                self.expect_argument_count(function_name, &arguments, 1, node_location.clone())?;
+
+                vec![Value::String(self.tostring(&arguments[0], node_location)?)]
+            }
+
+            "type" => {
+                self.expect_argument_count(function_name, &arguments, 1, node_location)?;
+
+                vec![Value::String(String::from(arguments[0].type_name()))]
+            }
+
+            // setmetatable(table, metatable), returns the table
+            "setmetatable" => {
+                let table =
+                    self.expect_table_argument(function_name, &arguments, node_location.clone())?;
+
+                table.borrow_mut().metatable = match arguments.get(1) {
+                    Some(Value::Table(metatable)) => Some(metatable.clone()),
+                    None | Some(Value::Nil) => None,
+
+                    Some(other) => return Err(self.error(
+                        ErrorCode::InvalidArgument,
+                        format!(
+                            "Invalid metatable of type '{}' for function '{}', expected 'table' or 'nil'",
+                            other.type_name(),
+                            function_name
+                        ),
+                        node_location,
+                    )),
+                };
+
+                vec![arguments[0].clone()]
+            }
+
+            "getmetatable" => match arguments.first() {
+                Some(Value::Table(table)) => vec![table
+                    .borrow()
+                    .metatable
+                    .clone()
+                    .map_or(Value::Nil, Value::Table)],
+                _ => vec![Value::Nil],
+            },
+
+            // rawget(table, key), ignoring `__index`
+            "rawget" => {
+                let table = self.expect_table_argument(function_name, &arguments, node_location)?;
+                let key = arguments.get(1).cloned().unwrap_or(Value::Nil);
+
+                let value = table.borrow().get(&key);
+                vec![value]
+            }
+
+            // rawset(table, key, value), ignoring `__newindex`, returns the table
+            "rawset" => {
+                let table =
+                    self.expect_table_argument(function_name, &arguments, node_location.clone())?;
+                let key = arguments.get(1).cloned().unwrap_or(Value::Nil);
+                let value = arguments.get(2).cloned().unwrap_or(Value::Nil);
+
+                self.check_table_key(&key, node_location)?;
+                table.borrow_mut().set(key, value);
+
+                vec![arguments[0].clone()]
+            }
+
+            // next(table, key)
+            "next" => {
+                let table =
+                    self.expect_table_argument(function_name, &arguments, node_location.clone())?;
+                let key = arguments.get(1).cloned().unwrap_or(Value::Nil);
+
+                let entry = table.borrow().next(&key);
+                match entry {
+                    Some(Some((key, value))) => vec![key, value],
+                    Some(None) => vec![Value::Nil],
+                    None => {
+                        return Err(self.error(
+                            ErrorCode::InvalidArgument,
+                            format!("Invalid key '{}' given to 'next'", key),
+                            node_location,
+                        ))
+                    }
+                }
+            }
+
+            // for key, value in pairs(table) { ... }
+            "pairs" => {
+                self.expect_table_argument(function_name, &arguments, node_location)?;
+
+                vec![
+                    Value::Function(Function::Builtin("next")),
+                    arguments[0].clone(),
+                    Value::Nil,
+                ]
+            }
+
+            // for index, value in ipairs(table) { ... }
+            "ipairs" => {
+                self.expect_table_argument(function_name, &arguments, node_location)?;
+
+                vec![
+                    Value::Function(Function::Builtin("ipairs_iterator")),
+                    arguments[0].clone(),
+                    Value::Number(0.0),
+                ]
+            }
+
+            // Iterates over 1, 2, ... until the first nil value
+            "ipairs_iterator" => {
+                let table = self.expect_table_argument(function_name, &arguments, node_location)?;
+                let index = match arguments.get(1) {
+                    Some(Value::Number(index)) => index + 1.0,
+                    _ => 1.0,
+                };
+
+                let value = table.borrow().get(&Value::Number(index));
+                match value {
+                    Value::Nil => vec![Value::Nil],
+                    value => vec![Value::Number(index), value],
+                }
+            }
+
+            "string.len" => {
+                let string =
+                    self.expect_string_argument(function_name, &arguments, 0, node_location)?;
+
+                vec![Value::Number(string.chars().count() as f64)]
+            }
+
+            "string.upper" => {
+                let string =
+                    self.expect_string_argument(function_name, &arguments, 0, node_location)?;
+
+                vec![Value::String(string.to_uppercase())]
+            }
+
+            "string.lower" => {
+                let string =
+                    self.expect_string_argument(function_name, &arguments, 0, node_location)?;
+
+                vec![Value::String(string.to_lowercase())]
+            }
+
+            // string.sub(s, start, stop), 1-based and inclusive, negative indices count from
+            // the end
+            "string.sub" => {
+                let string = self.expect_string_argument(
+                    function_name,
+                    &arguments,
+                    0,
+                    node_location.clone(),
+                )?;
+                let chars = string.chars().collect::<Vec<char>>();
+                let length = chars.len() as f64;
+
+                let start = self.expect_number_argument(
+                    function_name,
+                    &arguments,
+                    1,
+                    Some(1.0),
+                    node_location.clone(),
+                )?;
+                let stop = self.expect_number_argument(
+                    function_name,
+                    &arguments,
+                    2,
+                    Some(-1.0),
+                    node_location,
+                )?;
+
+                let start = if start < 0.0 {
+                    length + start + 1.0
+                } else {
+                    start
+                }
+                .max(1.0);
+                let stop = if stop < 0.0 {
+                    length + stop + 1.0
+                } else {
+                    stop
+                }
+                .min(length);
+
+                if start > stop {
+                    return Ok(vec![Value::String(String::new())]);
+                }
+
+                vec![Value::String(
+                    chars[start as usize - 1..stop as usize].iter().collect(),
+                )]
+            }
+
+            // string.rep(s, count)
+            "string.rep" => {
+                let string = self.expect_string_argument(
+                    function_name,
+                    &arguments,
+                    0,
+                    node_location.clone(),
+                )?;
+                let count = self.expect_number_argument(
+                    function_name,
+                    &arguments,
+                    1,
+                    None,
+                    node_location.clone(),
+                )?;
+
+                let count = count.max(0.0) as usize;
+                match string.len().checked_mul(count) {
+                    Some(length) if length <= MAX_STRING_LENGTH => {
+                        vec![Value::String(string.repeat(count))]
+                    }
+
+                    _ => {
+                        return Err(self.error(
+                            ErrorCode::InvalidArgument,
+                            format!(
+                                "Invalid argument #2 for function '{}', the repeated string would be longer than {} bytes",
+                                function_name, MAX_STRING_LENGTH
+                            ),
+                            node_location,
+                        ))
+                    }
+                }
+            }
+
+            "syscall" => {
+                if arguments.is_empty() {
+                    let mut diagnostic = self.error(
+                        ErrorCode::ArgumentCount,
+                        format!(
+                            "Insufficient amount of arguments for function '{}', at least 1 required.",
+                            function_name
+                        ),
+                        node_location,
+                    );
+                    diagnostic.help.push(String::from(
+                        "([command_name], [arguments]...)\nAll the arguments are strings.",
+                    ));
+                    return Err(diagnostic);
+                }
+
+                let mut command_list = Vec::<String>::new();
+
+                for argument in arguments.iter() {
+                    match argument {
+                        Value::String(_) | Value::Number(_) => {
+                            command_list.push(argument.to_string());
                         }
 
-                        Err(err) => {
-                            self.throw_err(format!(
-                                "Could not execute command.\nReason: {}",
-                                err
-                            ), node_location.clone());
+                        other => {
+                            return Err(self.error(
+                                ErrorCode::InvalidArgument,
+                                format!(
+                                    "Invalid argument of type '{}' for function '{}', expected 'string' or 'number'",
+                                    other.type_name(),
+                                    function_name
+                                ),
+                                node_location.clone(),
+                            ));
                         }
                     }
                 }
 
-                other => {
-                    self.throw_err(format!("Invalid function '{}'", other), node_location);
+                let mut process_command = std::process::Command::new(command_list[0].clone());
+                for command in command_list.iter().skip(1) {
+                    process_command.arg(command);
                 }
-            },
 
-            other => {
-                self.throw_err(format!("Unimplemented node '{:?}'", other), node_location);
+                match process_command.output() {
+                    Ok(ok) => {
+                        let mut stdout = io::stdout();
+                        stdout.write(&ok.stdout).and(stdout.flush()).ok();
+
+                        if !ok.stderr.is_empty() {
+                            stdout.write(&ok.stderr).and(stdout.flush()).ok();
+                        }
+                    }
+
+                    Err(err) => {
+                        return Err(self.error(
+                            ErrorCode::CommandFailed,
+                            format!("Could not execute command.\nReason: {}", err),
+                            node_location.clone(),
+                        ));
+                    }
+                }
+
+                Vec::new()
             }
+
+            other => unreachable!("Unknown builtin '{}'", other),
+        })
+    }
+
+    fn expect_table_argument(
+        &self,
+        function_name: &str,
+        arguments: &[Value],
+        node_location: Location,
+    ) -> Result<Rc<RefCell<Table>>, Box<Diagnostic>> {
+        match arguments.first() {
+            Some(Value::Table(table)) => Ok(table.clone()),
+
+            other => Err(self.error(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Invalid argument of type '{}' for function '{}', expected 'table'",
+                    other.map_or("nil", Value::type_name),
+                    function_name
+                ),
+                node_location,
+            )),
         }
     }
 
-    fn throw_err<T: Into<String>>(&self, msg: T, node_location: Location) {
-        let line_number_spaces = " ".repeat(node_location.start_line.to_string().len());
+    fn expect_string_argument(
+        &self,
+        function_name: &str,
+        arguments: &[Value],
+        index: usize,
+        node_location: Location,
+    ) -> Result<String, Box<Diagnostic>> {
+        match arguments.get(index) {
+            Some(Value::String(string)) => Ok(string.clone()),
 
-        println!("[Error]");
-        println!("{}\n", msg.into());
-        println!(
-            "[Location] {}:{}:{}",
-            node_location.file_path, node_location.start_line, node_location.start_col
-        );
-        println!(" {} |", line_number_spaces);
-        println!(" {} | {}", node_location.start_line, node_location.line);
-        println!(
-            " {} | {}",
-            line_number_spaces,
-            "^".repeat(node_location.line.len())
-        );
-        std::process::exit(1);
+            other => Err(self.error(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Invalid argument #{} of type '{}' for function '{}', expected 'string'",
+                    index + 1,
+                    other.map_or("nil", Value::type_name),
+                    function_name
+                ),
+                node_location,
+            )),
+        }
+    }
+
+    /// Like `expect_string_argument`, but a missing or nil argument falls back to `default`
+    fn expect_number_argument(
+        &self,
+        function_name: &str,
+        arguments: &[Value],
+        index: usize,
+        default: Option<f64>,
+        node_location: Location,
+    ) -> Result<f64, Box<Diagnostic>> {
+        match (arguments.get(index), default) {
+            (Some(Value::Number(number)), _) => Ok(number.floor()),
+            (None | Some(Value::Nil), Some(default)) => Ok(default),
+
+            (other, _) => Err(self.error(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Invalid argument #{} of type '{}' for function '{}', expected 'number'",
+                    index + 1,
+                    other.map_or("nil", Value::type_name),
+                    function_name
+                ),
+                node_location,
+            )),
+        }
+    }
+
+    fn expect_argument_count(
+        &self,
+        function_name: &str,
+        arguments: &[Value],
+        expected_count: usize,
+        node_location: Location,
+    ) -> Result<(), Box<Diagnostic>> {
+        if arguments.len() != expected_count {
+            return Err(self.error(
+                ErrorCode::ArgumentCount,
+                format!(
+                    "Function '{}' expects {} argument(s), but {} were given",
+                    function_name,
+                    expected_count,
+                    arguments.len()
+                ),
+                node_location,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// A variable in scope, builtin or value keyword like `true` that an unknown variable name
+    /// looks like a typo of
+    fn similar_variable(&self, variable_name: &str, only_functions: bool) -> Option<String> {
+        let environment = self.environment.borrow();
+        let names = environment.names();
+
+        let candidates = names.iter().map(String::as_str).filter(|name| {
+            !only_functions || matches!(environment.get(name), Some(Value::Function(_)))
+        });
+
+        if only_functions {
+            similar_name(variable_name, candidates)
+        } else {
+            similar_name(variable_name, candidates.chain(["nil", "true", "false"]))
+        }
+    }
+
+    /// Diagnostic at `node_location`, noting the calls that led to it
+    fn error<T: Into<String>>(
+        &self,
+        code: ErrorCode,
+        msg: T,
+        node_location: Location,
+    ) -> Box<Diagnostic> {
+        let diagnostic = Diagnostic::error(code, msg, node_location);
+
+        if self.call_stack.is_empty() {
+            return Box::new(diagnostic);
+        }
+
+        let mut call_stack = String::from("Call stack:");
+        for call_frame in self.call_stack.iter().rev().take(MAX_SHOWN_CALL_FRAMES) {
+            call_stack.push_str(&format!(
+                "\n in '{}' called at {}:{}:{}",
+                call_frame.function_name,
+                call_frame.location.file_path,
+                call_frame.location.start_line,
+                call_frame.location.start_col
+            ));
+        }
+
+        if self.call_stack.len() > MAX_SHOWN_CALL_FRAMES {
+            call_stack.push_str(&format!(
+                "\n ... and {} more",
+                self.call_stack.len() - MAX_SHOWN_CALL_FRAMES
+            ));
+        }
+
+        Box::new(diagnostic.with_note(call_stack))
+    }
+}
+
+/// Name of the metamethod for a binary operator, comparisons use `__lt` and `__le` with
+/// swapped operands for `>` and `>=`
+fn binary_event(operator: BinaryOperator) -> &'static str {
+    match operator {
+        BinaryOperator::Add => "__add",
+        BinaryOperator::Subtract => "__sub",
+        BinaryOperator::Multiply => "__mul",
+        BinaryOperator::Divide => "__div",
+        BinaryOperator::Modulo => "__mod",
+
+        BinaryOperator::Less | BinaryOperator::Greater => "__lt",
+        BinaryOperator::LessEqual | BinaryOperator::GreaterEqual => "__le",
+
+        BinaryOperator::Equal | BinaryOperator::NotEqual => "__eq",
+        BinaryOperator::And | BinaryOperator::Or => unreachable!(),
+    }
+}
+
+fn unary_event(operator: UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::Negate => "__unm",
+        UnaryOperator::Length => "__len",
+        UnaryOperator::Not => unreachable!(),
     }
 }
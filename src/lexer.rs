@@ -1,8 +1,23 @@
-use crate::tokens::{Location, Token, TokenKind};
+use unicode_ident::{is_xid_continue, is_xid_start};
+
+use crate::{
+    diagnostic::Diagnostic,
+    error_code::ErrorCode,
+    tokens::{Location, Token, TokenKind},
+};
+
+/// An interpolated string whose `${...}` expression is being lexed
+struct Interpolation {
+    // Unclosed '{' inside of the expression
+    brace_depth: usize,
+    start_line: usize,
+    start_col: usize,
+}
 
 pub struct Lexer {
     pub output_tokens: Vec<Token>,
 
+    // In chars, everything in the lexer is indexed by char rather than by byte
     source_code_length: usize,
     source_code_chars: Vec<char>,
     source_code_lines: Vec<String>,
@@ -10,6 +25,10 @@ pub struct Lexer {
     current_char_index: usize,
     line_start_indices: Vec<usize>,
     file_path: String,
+    interpolations: Vec<Interpolation>,
+
+    // Lexical errors found so far, lexing continues after each one
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Lexer {
@@ -17,137 +36,360 @@ impl Lexer {
         Self {
             output_tokens: Vec::new(),
 
-            source_code_length: source_code.len(),
+            source_code_length: source_code.chars().count(),
             source_code_chars: source_code.chars().collect(),
             source_code_lines: source_code.split("\n").map(String::from).collect(),
 
             current_char_index: 0,
             line_start_indices: vec![0],
             file_path,
+            interpolations: Vec::new(),
+
+            diagnostics: Vec::new(),
         }
     }
 
-    pub fn lex(&mut self) {
+    /// Lexes the whole source code, returning every lexical error found if there are any.
+    /// Invalid input becomes an `Error` token, so `output_tokens` is usable either way.
+    pub fn lex(&mut self) -> Result<(), Vec<Diagnostic>> {
         while self.is_not_eof() {
-            // Comments
-            if self.current_line().starts_with("//") { self.next(); continue; }
             match self.current_char() {
                 c if c.is_whitespace() => {}
 
-                c if c.is_alphabetic() => {
+                // Unicode identifiers follow the XID rules, like Rust, plus a leading '_'
+                c if is_xid_start(c) || c == '_' => {
                     self.eat_identifier();
                 }
 
-                '"' => {
-                    self.eat_string();
+                c if c.is_ascii_digit() => {
+                    let start_index = self.current_char_index;
+                    let start_line = self.current_line_number();
+                    let start_col = self.current_col();
+
+                    if let Err(diagnostic) = self.eat_number() {
+                        // Skip the rest of the broken literal, e.g. all of `12abc`
+                        while self.is_not_eof()
+                            && (self.current_char().is_alphanumeric() || self.current_char() == '_')
+                        {
+                            self.current_char_index += 1;
+                        }
+                        self.current_char_index -= 1;
+
+                        self.push_error(*diagnostic, start_index, start_line, start_col);
+                    }
                 }
 
-                '(' => {
-                    let current_col = self.current_col();
-                    let current_line_number = self.current_line_number();
+                '"' => {
+                    let start_line = self.current_line_number();
+                    let start_col = self.current_col();
+                    self.eat_string(start_line, start_col, false);
+                }
 
-                    self.output_tokens.push(Token {
-                        kind: TokenKind::OParen,
-                        value: String::from("("),
-                        location: Location {
-                            start_col: current_col,
-                            start_line: current_line_number,
+                // The '}' closing an interpolated expression continues its string
+                '}' if self
+                    .interpolations
+                    .last()
+                    .is_some_and(|interpolation| interpolation.brace_depth == 0) =>
+                {
+                    let interpolation = self.interpolations.pop().unwrap();
+                    self.eat_string(interpolation.start_line, interpolation.start_col, true);
+                }
 
-                            end_col: current_col,
-                            end_line: current_line_number,
+                '{' if !self.interpolations.is_empty() => {
+                    self.interpolations.last_mut().unwrap().brace_depth += 1;
+                    self.eat_symbol(TokenKind::OBrace, "{");
+                }
+                '}' if !self.interpolations.is_empty() => {
+                    self.interpolations.last_mut().unwrap().brace_depth -= 1;
+                    self.eat_symbol(TokenKind::CBrace, "}");
+                }
 
-                            file_path: self.file_path.clone(),
-                            line: self.current_line(),
-                        },
-                    })
+                '(' => self.eat_symbol(TokenKind::OParen, "("),
+                ')' => self.eat_symbol(TokenKind::CParen, ")"),
+                '{' => self.eat_symbol(TokenKind::OBrace, "{"),
+                '}' => self.eat_symbol(TokenKind::CBrace, "}"),
+                '[' if self.is_long_bracket() => self.eat_long_string(),
+                '[' => self.eat_symbol(TokenKind::OBracket, "["),
+                ']' => self.eat_symbol(TokenKind::CBracket, "]"),
+                '.' => self.eat_symbol(TokenKind::Dot, "."),
+                ':' => self.eat_symbol(TokenKind::Colon, ":"),
+                ',' => self.eat_symbol(TokenKind::Comma, ","),
+                ';' => self.eat_symbol(TokenKind::Semicolon, ";"),
+
+                '+' => self.eat_symbol(TokenKind::Plus, "+"),
+                '-' => self.eat_symbol(TokenKind::Minus, "-"),
+                '*' => self.eat_symbol(TokenKind::Star, "*"),
+                '/' if self.peek_char() == Some('/') => self.eat_line_comment(),
+                '/' if self.peek_char() == Some('*') => self.eat_block_comment(),
+                '/' => self.eat_symbol(TokenKind::Slash, "/"),
+                '%' => self.eat_symbol(TokenKind::Percent, "%"),
+                '#' => self.eat_symbol(TokenKind::Hash, "#"),
+
+                '=' if self.peek_char() == Some('=') => {
+                    self.eat_symbol(TokenKind::EqualEqual, "==")
                 }
+                '!' if self.peek_char() == Some('=') => self.eat_symbol(TokenKind::BangEqual, "!="),
+                '<' if self.peek_char() == Some('=') => self.eat_symbol(TokenKind::LessEqual, "<="),
+                '>' if self.peek_char() == Some('=') => {
+                    self.eat_symbol(TokenKind::GreaterEqual, ">=")
+                }
+                '=' => self.eat_symbol(TokenKind::Equal, "="),
+                '<' => self.eat_symbol(TokenKind::Less, "<"),
+                '>' => self.eat_symbol(TokenKind::Greater, ">"),
+
+                other => {
+                    let diagnostic = self.error(
+                        ErrorCode::UnexpectedCharacter,
+                        format!("Unexpected character '{}'", other),
+                    );
+                    self.push_error(
+                        *diagnostic,
+                        self.current_char_index,
+                        self.current_line_number(),
+                        self.current_col(),
+                    );
+                }
+            }
 
-                ')' => {
-                    let current_col = self.current_col();
-                    let current_line_number = self.current_line_number();
+            self.next();
+        }
 
-                    self.output_tokens.push(Token {
-                        kind: TokenKind::CParen,
-                        value: String::from(")"),
-                        location: Location {
-                            start_col: current_col,
-                            start_line: current_line_number,
+        if let Some(interpolation) = self.interpolations.last() {
+            let diagnostic = self.error(
+                ErrorCode::UnclosedInterpolation,
+                format!(
+                    "Missing '}}' for the interpolation in the string since line {} at column {}",
+                    interpolation.start_line, interpolation.start_col
+                ),
+            );
+            self.push_error(
+                *diagnostic,
+                self.current_char_index,
+                self.current_line_number(),
+                self.current_col(),
+            );
+        }
 
-                            end_col: current_col,
-                            end_line: current_line_number,
+        if self.diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.diagnostics))
+        }
+    }
 
-                            file_path: self.file_path.clone(),
-                            line: self.current_line(),
-                        },
-                    })
-                }
+    fn eat_identifier(&mut self) {
+        let start_col = self.current_col();
+        let start_line = self.current_line_number();
 
-                ',' => {
-                    let current_col = self.current_col();
-                    let current_line_number = self.current_line_number();
+        let mut eaten_identifier = String::new();
 
-                    self.output_tokens.push(Token {
-                        kind: TokenKind::Comma,
-                        value: String::from(","),
-                        location: Location {
-                            start_col: current_col,
-                            start_line: current_line_number,
+        while self.is_not_eof() && is_xid_continue(self.current_char()) {
+            eaten_identifier.push(self.current_char());
+            self.current_char_index += 1;
+        }
 
-                            end_col: current_col,
-                            end_line: current_line_number,
+        // Stay on the last character of the identifier
+        self.current_char_index -= 1;
 
-                            file_path: self.file_path.clone(),
-                            line: self.current_line(),
-                        },
-                    })
-                }
+        self.output_tokens.push(Token {
+            kind: TokenKind::keyword(&eaten_identifier).unwrap_or(TokenKind::Identifier),
+            value: eaten_identifier,
+            location: Location {
+                start_col,
+                start_line,
 
-                ';' => {
-                    let current_col = self.current_col();
-                    let current_line_number = self.current_line_number();
+                end_col: self.current_col(),
+                end_line: self.current_line_number(),
 
-                    self.output_tokens.push(Token {
-                        kind: TokenKind::Semicolon,
-                        value: String::from(";"),
-                        location: Location {
-                            start_col: current_col,
-                            start_line: current_line_number,
+                file_path: self.file_path.clone(),
+                line: self.current_line(),
+            },
+        })
+    }
 
-                            end_col: current_col,
-                            end_line: current_line_number,
+    /// Eats a `//` comment until the end of the line, `///` doc comments are kept as tokens
+    fn eat_line_comment(&mut self) {
+        let start_col = self.current_col();
+        let start_line = self.current_line_number();
+        let comment_start_index = self.current_char_index;
 
-                            file_path: self.file_path.clone(),
-                            line: self.current_line(),
-                        },
-                    })
-                }
+        while self.peek_char().is_some_and(|c| c != '\n') {
+            self.current_char_index += 1;
+        }
 
-                other => self.throw_err(format!("Unexpected character '{}'", other)),
+        let comment = self.source_code_chars[comment_start_index..=self.current_char_index]
+            .iter()
+            .collect::<String>();
+
+        // Like Rust, four or more slashes are a regular comment
+        if comment.starts_with("///") && !comment.starts_with("////") {
+            self.output_tokens.push(Token {
+                kind: TokenKind::DocComment,
+                value: String::from(comment[3..].trim()),
+                location: Location {
+                    start_col,
+                    start_line,
+
+                    end_col: self.current_col(),
+                    end_line: start_line,
+
+                    file_path: self.file_path.clone(),
+                    line: self.current_line(),
+                },
+            })
+        }
+    }
+
+    /// Eats a `/* */` comment, which can be nested and span multiple lines
+    fn eat_block_comment(&mut self) {
+        let start_index = self.current_char_index;
+        let start_col = self.current_col();
+        let start_line = self.current_line_number();
+
+        // Skip the opening '/*'
+        self.next();
+        self.next();
+
+        let mut depth = 1;
+        loop {
+            if !self.is_not_eof() {
+                let diagnostic = self.error(
+                    ErrorCode::UnterminatedComment,
+                    format!(
+                        "Missing end of block comment '*/' since line {} at column {}",
+                        start_line, start_col,
+                    ),
+                );
+                self.push_error(*diagnostic, start_index, start_line, start_col);
+                return;
+            }
+
+            match (self.current_char(), self.peek_char()) {
+                ('/', Some('*')) => {
+                    depth += 1;
+                    self.next();
+                }
+                ('*', Some('/')) => {
+                    depth -= 1;
+                    self.next();
+
+                    // Stay on the closing '/'
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
             }
 
             self.next();
         }
     }
 
-    fn eat_identifier(&mut self) {
+    /// Eats a symbol made up of one or more characters, e.g. '(' or '<='
+    fn eat_symbol(&mut self, kind: TokenKind, symbol: &str) {
+        let start_col = self.current_col();
+        let current_line_number = self.current_line_number();
+
+        self.current_char_index += symbol.chars().count() - 1;
+
+        self.output_tokens.push(Token {
+            kind,
+            value: String::from(symbol),
+            location: Location {
+                start_col,
+                start_line: current_line_number,
+
+                end_col: self.current_col(),
+                end_line: current_line_number,
+
+                file_path: self.file_path.clone(),
+                line: self.current_line(),
+            },
+        })
+    }
+
+    fn eat_number(&mut self) -> Result<(), Box<Diagnostic>> {
         let start_col = self.current_col();
         let start_line = self.current_line_number();
 
-        let mut eaten_identifier = String::new();
+        // 0x1F, 0b1010
+        let radix = match (self.current_char(), self.peek_char()) {
+            ('0', Some('x' | 'X')) => 16,
+            ('0', Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+
+        let mut eaten_number = String::new();
+
+        let number = if radix == 10 {
+            self.eat_digits(&mut eaten_number, 10);
+
+            // Fractional part, only if a digit follows the '.'
+            if self.is_not_eof()
+                && self.current_char() == '.'
+                && self.peek_char().is_some_and(|c| c.is_ascii_digit())
+            {
+                eaten_number.push('.');
+                self.current_char_index += 1;
+                self.eat_digits(&mut eaten_number, 10);
+            }
 
-        while self.current_char().is_alphanumeric() {
-            eaten_identifier.push(self.current_char());
-            if self.is_eof() {
-                break;
+            // Exponent, e.g. 1e10, 2.5E-3
+            if self.is_not_eof() && matches!(self.current_char(), 'e' | 'E') {
+                eaten_number.push('e');
+                self.current_char_index += 1;
+
+                if self.is_not_eof() && matches!(self.current_char(), '+' | '-') {
+                    eaten_number.push(self.current_char());
+                    self.current_char_index += 1;
+                }
+
+                if !self.is_not_eof() || !self.current_char().is_ascii_digit() {
+                    return Err(self.error(
+                        ErrorCode::InvalidNumber,
+                        "Missing digits in exponent of number literal",
+                    ));
+                }
+
+                self.eat_digits(&mut eaten_number, 10);
             }
-            self.current_char_index += 1;
+
+            eaten_number.parse::<f64>().unwrap()
+        } else {
+            // Skip the '0x' / '0b' prefix
+            self.current_char_index += 2;
+            self.eat_digits(&mut eaten_number, radix);
+
+            if eaten_number.is_empty() {
+                return Err(self.error(
+                    ErrorCode::InvalidNumber,
+                    format!(
+                        "Missing digits after '0{}' in number literal",
+                        if radix == 16 { 'x' } else { 'b' }
+                    ),
+                ));
+            }
+
+            match u64::from_str_radix(&eaten_number, radix) {
+                Ok(number) => number as f64,
+                Err(_) => {
+                    return Err(self.error(ErrorCode::InvalidNumber, "Number literal is too large"))
+                }
+            }
+        };
+
+        if self.is_not_eof() && self.current_char().is_alphanumeric() {
+            return Err(self.error(
+                ErrorCode::InvalidNumber,
+                format!("Invalid digit '{}' in number literal", self.current_char()),
+            ));
         }
 
         self.current_char_index -= 1;
 
         self.output_tokens.push(Token {
-            kind: TokenKind::Identifier,
-            value: eaten_identifier,
+            kind: TokenKind::Number,
+            value: number.to_string(),
             location: Location {
                 start_col,
                 start_line,
@@ -158,31 +400,161 @@ impl Lexer {
                 file_path: self.file_path.clone(),
                 line: self.current_line(),
             },
-        })
+        });
+
+        Ok(())
     }
 
-    fn eat_string(&mut self) {
-        let start_col = self.current_col();
-        let start_line = self.current_line_number();
+    /// Eats digits of the given radix into `eaten_number`, skipping '_' separators
+    fn eat_digits(&mut self, eaten_number: &mut String, radix: u32) {
+        while self.is_not_eof()
+            && (self.current_char().is_digit(radix) || self.current_char() == '_')
+        {
+            if self.current_char() != '_' {
+                eaten_number.push(self.current_char());
+            }
+            self.current_char_index += 1;
+        }
+    }
+
+    /// Eats a string starting at its '"', or continuing at the '}' after an interpolated
+    /// expression. `start_line` and `start_col` are where the whole string starts.
+    fn eat_string(&mut self, start_line: usize, start_col: usize, is_continuation: bool) {
+        let token_start_index = self.current_char_index;
+        let token_start_col = self.current_col();
+        let token_start_line = self.current_line_number();
 
         let mut eaten_string = String::new();
         self.current_char_index += 1;
 
-        while self.current_char() != '"' {
+        // An escape may have moved past the end of the file
+        while !self.is_not_eof() || self.current_char() != '"' {
             if self.is_eof() {
-                self.throw_err(format!(
-                    "Missing end of string '\"' since line {} at column {}",
-                    start_line, start_col,
-                ))
+                let diagnostic = self.error(
+                    ErrorCode::UnterminatedString,
+                    format!(
+                        "Missing end of string '\"' since line {} at column {}",
+                        start_line, start_col,
+                    ),
+                );
+                self.push_error(
+                    *diagnostic,
+                    token_start_index,
+                    token_start_line,
+                    token_start_col,
+                );
+                return;
+            }
+
+            // "${expression}", the expression is lexed as usual until its closing '}'
+            if self.current_char() == '$' && self.peek_char() == Some('{') {
+                self.current_char_index += 1;
+                self.interpolations.push(Interpolation {
+                    brace_depth: 0,
+                    start_line,
+                    start_col,
+                });
+
+                self.push_string_token(
+                    if is_continuation {
+                        TokenKind::InterpolationMiddle
+                    } else {
+                        TokenKind::InterpolationStart
+                    },
+                    eaten_string,
+                    token_start_line,
+                    token_start_col,
+                );
+                return;
+            }
+
+            if self.current_char() == '\\' {
+                // A broken escape is reported and skipped, the rest of the string is kept
+                match self.eat_escape() {
+                    Ok(escaped_char) => eaten_string.push(escaped_char),
+                    Err(diagnostic) => self.diagnostics.push(*diagnostic),
+                }
+            } else {
+                eaten_string.push(self.current_char());
+            }
+            self.next();
+        }
+
+        self.push_string_token(
+            if is_continuation {
+                TokenKind::InterpolationEnd
+            } else {
+                TokenKind::String
+            },
+            eaten_string,
+            token_start_line,
+            token_start_col,
+        );
+    }
+
+    /// Eats a raw string in long brackets, e.g. [[C:\path]] or [==[ can contain ]] ]==].
+    /// Nothing is escaped, and a newline right after the opening bracket is skipped.
+    fn eat_long_string(&mut self) {
+        let start_index = self.current_char_index;
+        let start_col = self.current_col();
+        let start_line = self.current_line_number();
+
+        let mut level = 0;
+        self.next();
+        while self.current_char() == '=' {
+            level += 1;
+            self.next();
+        }
+        self.next();
+
+        if self.is_not_eof() && self.current_char() == '\r' && self.peek_char() == Some('\n') {
+            self.next();
+        }
+        if self.is_not_eof() && self.current_char() == '\n' {
+            self.next();
+        }
+
+        let closing_bracket = format!("]{}]", "=".repeat(level))
+            .chars()
+            .collect::<Vec<char>>();
+        let mut eaten_string = String::new();
+
+        while !self.source_code_chars[self.current_char_index..].starts_with(&closing_bracket) {
+            if !self.is_not_eof() {
+                let diagnostic = self.error(
+                    ErrorCode::UnterminatedString,
+                    format!(
+                        "Missing end of string '{}' since line {} at column {}",
+                        closing_bracket.iter().collect::<String>(),
+                        start_line,
+                        start_col,
+                    ),
+                );
+                self.push_error(*diagnostic, start_index, start_line, start_col);
+                return;
             }
 
             eaten_string.push(self.current_char());
-            self.current_char_index += 1;
+            self.next();
         }
 
+        // Stay on the last ']'
+        self.current_char_index += closing_bracket.len() - 1;
+
+        self.push_string_token(TokenKind::String, eaten_string, start_line, start_col);
+    }
+
+    /// Pushes a string or a part of an interpolated string, ending at the current character
+    fn push_string_token(
+        &mut self,
+        kind: TokenKind,
+        value: String,
+        start_line: usize,
+        start_col: usize,
+    ) {
         self.output_tokens.push(Token {
-            kind: TokenKind::String,
-            value: eaten_string,
+            kind,
+            value,
             location: Location {
                 start_col,
                 start_line,
@@ -191,18 +563,185 @@ impl Lexer {
                 end_line: self.current_line_number(),
 
                 file_path: self.file_path.clone(),
-                line: self.current_line(),
+                // The string may span multiple lines, diagnostics show where it starts
+                line: self.source_code_lines[start_line - 1].clone(),
             },
         })
     }
 
-    fn next(&mut self) {
+    /// Records a lexical error and pushes an `Error` token with the invalid input, from
+    /// `start_index` until the current character
+    fn push_error(
+        &mut self,
+        diagnostic: Diagnostic,
+        start_index: usize,
+        start_line: usize,
+        start_col: usize,
+    ) {
+        self.diagnostics.push(diagnostic);
+
+        // Unterminated strings and comments end past the last character
+        let end_index = (self.current_char_index + 1).min(self.source_code_length);
+
+        self.output_tokens.push(Token {
+            kind: TokenKind::Error,
+            value: self.source_code_chars[start_index.min(end_index)..end_index]
+                .iter()
+                .collect(),
+            location: Location {
+                start_col,
+                start_line,
+
+                end_col: self.current_col(),
+                end_line: self.current_line_number(),
+
+                file_path: self.file_path.clone(),
+                line: self.source_code_lines[start_line - 1].clone(),
+            },
+        })
+    }
+
+    /// Eats an escape sequence starting at the current '\\', leaving the cursor on its last
+    /// character
+    fn eat_escape(&mut self) -> Result<char, Box<Diagnostic>> {
+        let escape_index = self.current_char_index;
         self.current_char_index += 1;
 
+        if !self.is_not_eof() {
+            self.current_char_index = escape_index;
+            return Err(self.error(
+                ErrorCode::InvalidEscape,
+                "Missing escape character after '\\'",
+            ));
+        }
+
+        let escaped_char = match self.current_char() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '$' => '$',
+
+            // \x41, at most 0x7F so the string stays valid UTF-8
+            'x' => {
+                let mut digits = String::new();
+                for _ in 0..2 {
+                    match self.peek_char() {
+                        Some(c) if c.is_ascii_hexdigit() => {
+                            digits.push(c);
+                            self.current_char_index += 1;
+                        }
+                        _ => {
+                            self.current_char_index = escape_index;
+                            return Err(self.error(
+                                ErrorCode::InvalidEscape,
+                                "Escape '\\x' must be followed by two hex digits",
+                            ));
+                        }
+                    }
+                }
+
+                let code = u8::from_str_radix(&digits, 16).unwrap();
+                if code > 0x7F {
+                    self.current_char_index = escape_index;
+                    return Err(self.error(
+                        ErrorCode::InvalidEscape,
+                        format!(
+                            "Escape '\\x{}' is out of range, it must be at most '\\x7F'",
+                            digits
+                        ),
+                    ));
+                }
+
+                code as char
+            }
+
+            // \u{1F600}
+            'u' => {
+                if self.peek_char() != Some('{') {
+                    self.current_char_index = escape_index;
+                    return Err(self.error(
+                        ErrorCode::InvalidEscape,
+                        "Escape '\\u' must be followed by '{'",
+                    ));
+                }
+                self.current_char_index += 1;
+
+                let mut digits = String::new();
+                loop {
+                    match self.peek_char() {
+                        Some('}') => {
+                            self.current_char_index += 1;
+                            break;
+                        }
+                        Some(c) if c.is_ascii_hexdigit() && digits.len() < 6 => {
+                            digits.push(c);
+                            self.current_char_index += 1;
+                        }
+                        _ => {
+                            self.current_char_index = escape_index;
+                            return Err(self.error(
+                                ErrorCode::InvalidEscape,
+                                "Escape '\\u{...}' must contain 1 to 6 hex digits followed by '}'",
+                            ));
+                        }
+                    }
+                }
+
+                if digits.is_empty() {
+                    self.current_char_index = escape_index;
+                    return Err(self.error(
+                        ErrorCode::InvalidEscape,
+                        "Escape '\\u{}' must contain at least one hex digit",
+                    ));
+                }
+
+                match char::from_u32(u32::from_str_radix(&digits, 16).unwrap()) {
+                    Some(c) => c,
+                    None => {
+                        self.current_char_index = escape_index;
+                        return Err(self.error(
+                            ErrorCode::InvalidEscape,
+                            format!(
+                                "Escape '\\u{{{}}}' is not a valid unicode character",
+                                digits
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            other => {
+                self.current_char_index = escape_index;
+                return Err(self.error(
+                    ErrorCode::InvalidEscape,
+                    format!("Unknown escape '\\{}' in string", other),
+                ));
+            }
+        };
+
+        Ok(escaped_char)
+    }
+
+    /// Moves onto the next character, this must be used to move past a '\n'
+    fn next(&mut self) {
         if self.is_not_eof() && self.current_char() == '\n' {
-            self.line_start_indices.push(self.current_char_index);
-            self.next();
+            self.line_start_indices.push(self.current_char_index + 1);
+        }
+
+        self.current_char_index += 1;
+    }
+
+    /// Whether the current '[' opens a long bracket, e.g. [[ or [==[
+    fn is_long_bracket(&self) -> bool {
+        let mut index = self.current_char_index + 1;
+        while self.source_code_chars.get(index) == Some(&'=') {
+            index += 1;
         }
+
+        self.source_code_chars.get(index) == Some(&'[')
     }
 
     #[inline]
@@ -216,8 +755,15 @@ impl Lexer {
     }
 
     #[inline]
-    fn current_char(&mut self) -> char {
-        self.source_code_chars[self.current_char_index].clone()
+    fn current_char(&self) -> char {
+        self.source_code_chars[self.current_char_index]
+    }
+
+    #[inline]
+    fn peek_char(&self) -> Option<char> {
+        self.source_code_chars
+            .get(self.current_char_index + 1)
+            .copied()
     }
 
     #[inline]
@@ -235,22 +781,24 @@ impl Lexer {
         self.source_code_lines[self.current_line_number() - 1].clone()
     }
 
-    fn throw_err<T: Into<String>>(&self, msg: T) {
+    /// Diagnostic pointing at the current character
+    fn error<T: Into<String>>(&self, code: ErrorCode, msg: T) -> Box<Diagnostic> {
         let current_line_number = self.current_line_number();
-        let current_line_number_spaces = " ".repeat(current_line_number.to_string().len());
         let current_col = self.current_col();
-        let arrow_spaces = " ".repeat(current_col - 1);
 
-        println!("[Error]");
-        println!("{}\n", msg.into());
-        println!(
-            "[Location] {}:{}:{}",
-            self.file_path, current_line_number, current_col
-        );
-        println!(" {} |", current_line_number_spaces);
-        println!(" {} | {}", current_line_number, self.current_line(),);
-        println!(" {} | {}^", current_line_number_spaces, arrow_spaces);
+        Box::new(Diagnostic::error(
+            code,
+            msg,
+            Location {
+                start_col: current_col,
+                end_col: current_col,
+
+                start_line: current_line_number,
+                end_line: current_line_number,
 
-        std::process::exit(1);
+                file_path: self.file_path.clone(),
+                line: self.current_line(),
+            },
+        ))
     }
 }
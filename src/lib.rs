@@ -0,0 +1,30 @@
+//! The minute language: a `Lexer` turns source code into tokens, a `Parser` turns the tokens
+//! into nodes, and an `Interpreter` runs the nodes. Each step reports problems as
+//! `Diagnostic`s rather than exiting, so it can be embedded in other programs. They all
+//! recurse into nested code, so deeply nested code needs a thread with a bigger stack than
+//! the default, like the one the command line interpreter runs them on.
+//!
+//! ```
+//! use minute_lang::{interpreter::Interpreter, lexer::Lexer, parser::Parser};
+//!
+//! let mut lexer = Lexer::new(String::from("let x = 1 + 2;"), String::from("main.min"));
+//! lexer.lex().unwrap();
+//!
+//! let mut parser = Parser::new(lexer.output_tokens);
+//! parser.parse().unwrap();
+//!
+//! let mut interpreter = Interpreter::new(parser.output_nodes);
+//! interpreter.interpret().unwrap();
+//! ```
+
+pub mod ast;
+pub mod diagnostic;
+pub mod environment;
+pub mod error_code;
+pub mod interpreter;
+pub mod lexer;
+pub mod parser;
+pub mod suggestion;
+pub mod table;
+pub mod tokens;
+pub mod value;
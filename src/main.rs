@@ -1,26 +1,118 @@
-use std::{env, fs};
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    process, thread,
+};
 
-pub mod ast;
-pub mod tokens;
+use minute_lang::{
+    diagnostic::{error_limit_note, Diagnostic, Renderer},
+    error_code::ErrorCode,
+    interpreter::Interpreter,
+    lexer::Lexer,
+    parser::{Parser, DEFAULT_MAX_ERRORS},
+};
 
-mod lexer;
-use lexer::Lexer;
+// Stack of the thread running the program. The lexer, parser and interpreter recurse into
+// nested code, up to their limits for nesting and calls, which needs more than the main thread
+// has. Only the part that is used is actually allocated.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
-mod parser;
-use parser::Parser;
+/// How diagnostics are shown, set with `--error-format`
+#[derive(Default, PartialEq)]
+enum ErrorFormat {
+    #[default]
+    Human,
+    // One JSON object per line, for editors and CI
+    Json,
+}
+
+/// Command line options, e.g. `minute --max-errors=5 script.min`
+#[derive(Default)]
+struct Options {
+    source_file: Option<String>,
+    max_errors: Option<usize>,
+    error_format: ErrorFormat,
+    // Error code to explain instead of running a file, e.g. "M0102"
+    explain: Option<String>,
+}
 
-mod interpreter;
-use interpreter::Interpreter;
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--explain" {
+                match args.next() {
+                    Some(code) => options.explain = Some(code.clone()),
+                    None => return Err(String::from("Missing error code after '--explain'")),
+                }
+            } else if let Some(max_errors) = arg.strip_prefix("--max-errors=") {
+                match max_errors.parse::<usize>() {
+                    Ok(max_errors) if max_errors > 0 => options.max_errors = Some(max_errors),
+                    _ => {
+                        return Err(format!(
+                            "Invalid value '{}' for '--max-errors', expected a positive number",
+                            max_errors
+                        ))
+                    }
+                }
+            } else if let Some(error_format) = arg.strip_prefix("--error-format=") {
+                options.error_format = match error_format {
+                    "human" => ErrorFormat::Human,
+                    "json" => ErrorFormat::Json,
+                    _ => {
+                        return Err(format!(
+                            "Invalid value '{}' for '--error-format', expected 'human' or 'json'",
+                            error_format
+                        ))
+                    }
+                };
+            } else if arg.starts_with("--") {
+                return Err(format!("Unknown option '{}'", arg));
+            } else if options.source_file.is_none() {
+                options.source_file = Some(arg.clone());
+            } else {
+                return Err(format!("Unexpected argument '{}'", arg));
+            }
+        }
+
+        Ok(options)
+    }
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("[Usage]\n{} [source_file]", args[0]);
+    let usage = format!(
+        "[Usage]\n{} [--max-errors=N] [--error-format=human|json] [source_file]\n{} --explain CODE\n\n[Options]\n--max-errors=N         Stop after N lexical and syntax errors, 20 by default\n--error-format=FORMAT  Show errors as 'human' readable text, the default, or 'json'\n--explain CODE         Explain an error code, e.g. M0102",
+        args[0], args[0]
+    );
+
+    let options = match Options::parse(&args[1..]) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("[Error]\n{}\n\n{}", err, usage);
+            process::exit(1);
+        }
+    };
+
+    if let Some(code) = options.explain {
+        let Some(error_code) = ErrorCode::from_code(&code) else {
+            eprintln!("[Error]\nUnknown error code '{}'", code);
+            process::exit(1);
+        };
+
+        explain(error_code);
         return;
     }
 
+    let Some(source_file) = options.source_file.clone() else {
+        println!("{}", usage);
+        return;
+    };
+
     let source = {
-        if let Ok(source) = fs::read_to_string(&args[1]) {
+        if let Ok(source) = fs::read_to_string(&source_file) {
             source
         } else {
             println!("[Error]\nCould not open file");
@@ -28,16 +120,111 @@ fn main() {
         }
     };
 
-    let mut lexer = Lexer::new(source, args[1].clone());
-    lexer.lex();
+    let result = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || run(source.clone(), source_file, &options))
+            .expect("the thread running the program should start")
+            .join()
+    });
+    // A panic was already printed by the thread
+    let Ok(result) = result else {
+        process::exit(101);
+    };
+
+    if let Err(diagnostics) = result {
+        let renderer = Renderer::new(&source).with_colour(use_colour());
+
+        if options.error_format == ErrorFormat::Json {
+            for diagnostic in diagnostics.iter() {
+                eprintln!("{}", renderer.render_json(diagnostic));
+            }
+            process::exit(1);
+        }
+
+        for (index, diagnostic) in diagnostics.iter().enumerate() {
+            if index > 0 {
+                eprintln!();
+            }
+            eprint!("{}", renderer.render(diagnostic));
+        }
+
+        if diagnostics.len() > 1 {
+            eprintln!("\nFound {} errors", diagnostics.len());
+        }
+        eprintln!(
+            "\nFor more information about an error, run '{} --explain {}'",
+            args[0], diagnostics[0].code
+        );
+
+        process::exit(1);
+    }
+}
+
+fn run(source: String, file_path: String, options: &Options) -> Result<(), Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(source, file_path);
+    let lexed = lexer.lex();
 
     // dbg!(&lexer.output_tokens);
 
+    // Even with lexical errors the tokens are parsed, to report syntax errors too
     let mut parser = Parser::new(lexer.output_tokens);
-    parser.parse();
+    if let Some(max_errors) = options.max_errors {
+        parser = parser.with_max_errors(max_errors);
+    }
+    let parsed = parser.parse();
+
+    let mut diagnostics = lexed.err().unwrap_or_default();
+    diagnostics.extend(parsed.err().unwrap_or_default());
+    if !diagnostics.is_empty() {
+        diagnostics.sort_by_key(|diagnostic| {
+            (
+                diagnostic.location.start_line,
+                diagnostic.location.start_col,
+            )
+        });
+
+        // The limit is for lexical and syntax errors together, only the last one notes it
+        let max_errors = options.max_errors.unwrap_or(DEFAULT_MAX_ERRORS);
+        let limit_note = error_limit_note(max_errors);
+        let mut reached_limit = diagnostics.len() > max_errors;
+        diagnostics.truncate(max_errors);
+        for diagnostic in diagnostics.iter_mut() {
+            reached_limit |= diagnostic.notes.contains(&limit_note);
+            diagnostic.notes.retain(|note| *note != limit_note);
+        }
+        if reached_limit {
+            diagnostics.last_mut().unwrap().notes.push(limit_note);
+        }
+
+        return Err(diagnostics);
+    }
 
     // dbg!(&parser.output_nodes);
 
     let mut interpreter = Interpreter::new(parser.output_nodes);
-    interpreter.interpret();
+    interpreter
+        .interpret()
+        .map_err(|diagnostic| vec![*diagnostic])
+}
+
+/// Prints the longer description of an error code, with an example of the mistake and its fix
+fn explain(error_code: ErrorCode) {
+    let explanation = error_code.explanation();
+
+    println!("[{}] {}\n", error_code, explanation.title);
+    println!("{}", explanation.description);
+
+    if !explanation.example.is_empty() {
+        println!("\n[Example]\n{}", explanation.example);
+    }
+    if !explanation.fix.is_empty() {
+        println!("\n[Fix]\n{}", explanation.fix);
+    }
+}
+
+/// Colours are only used in a terminal, and can be turned off with `NO_COLOR`
+fn use_colour() -> bool {
+    io::stderr().is_terminal()
+        && env::var_os("NO_COLOR").is_none_or(|no_colour| no_colour.is_empty())
 }
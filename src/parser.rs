@@ -1,88 +1,958 @@
+use std::rc::Rc;
+
 use crate::{
-    ast::{Node, NodeKind},
+    ast::{BinaryOperator, Node, NodeKind, UnaryOperator},
+    diagnostic::{error_limit_note, Diagnostic},
+    error_code::ErrorCode,
+    suggestion::similar_name,
     tokens::{Token, TokenKind},
 };
 
+// How many syntax errors are reported before giving up, unless set with `with_max_errors`
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
+// How deeply expressions and blocks can be nested, deeper ones would overflow the stack while
+// parsing or running them
+const MAX_NESTING_DEPTH: usize = 200;
+
 pub struct Parser {
     pub output_nodes: Vec<Node>,
+    // Syntax errors found so far, parsing continues after each one
+    diagnostics: Vec<Diagnostic>,
+    max_errors: usize,
 
     input_tokens: Vec<Token>,
     input_tokens_length: usize,
     current_token_index: usize,
-}
 
-const NEXT_ARGUMENT_TOKENS: [TokenKind; 3] =
-    [TokenKind::CParen, TokenKind::String, TokenKind::Comma];
+    // How many loops the current statement is nested in, reset inside of functions
+    loop_depth: usize,
+    // How many functions the current statement is nested in
+    function_depth: usize,
+    // How many expressions and blocks the current token is nested in
+    nesting_depth: usize,
+}
 
 impl Parser {
     pub fn new(input_tokens: Vec<Token>) -> Self {
+        let input_tokens = input_tokens
+            .into_iter()
+            .filter(|token| token.kind != TokenKind::DocComment)
+            .collect::<Vec<Token>>();
+
         Self {
             input_tokens_length: input_tokens.len(),
             input_tokens,
             current_token_index: 0,
 
+            loop_depth: 0,
+            function_depth: 0,
+            nesting_depth: 0,
+
             output_nodes: Vec::new(),
+            diagnostics: Vec::new(),
+            max_errors: DEFAULT_MAX_ERRORS,
         }
     }
 
-    pub fn parse(&mut self) {
+    /// Stops parsing once `max_errors` syntax errors are found
+    pub fn with_max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = max_errors.max(1);
+        self
+    }
+
+    /// Parses all statements, returning every syntax error found if there are any
+    pub fn parse(&mut self) -> Result<(), Vec<Diagnostic>> {
         while self.is_not_last_token() {
-            let current_token = self.current_token();
-            match current_token.kind {
-                // This Identifier only refers to the function name
-                // E.g. print()
-                //      ^^^^^
-                TokenKind::Identifier => {
-                    let function_name = self.current_token().value;
-
-                    self.expect_next(TokenKind::OParen);
-                    self.next();
-                    self.expect_next_either(&NEXT_ARGUMENT_TOKENS);
-                    self.next();
+            let (loop_depth, function_depth, nesting_depth) =
+                (self.loop_depth, self.function_depth, self.nesting_depth);
+            let statement_start = self.current_token_index;
+
+            match self.parse_statement() {
+                Ok(statement) => self.output_nodes.push(statement),
+
+                Err(_) if self.is_at_error_token() => {
+                    self.loop_depth = loop_depth;
+                    self.function_depth = function_depth;
+                    self.nesting_depth = nesting_depth;
+                    self.synchronize(statement_start, false);
+                }
+
+                Err(diagnostic) => {
+                    self.diagnostics.push(*diagnostic);
+
+                    if self.diagnostics.len() >= self.max_errors {
+                        if self.peek().is_some() {
+                            let diagnostic = self
+                                .diagnostics
+                                .pop()
+                                .unwrap()
+                                .with_note(error_limit_note(self.max_errors));
+                            self.diagnostics.push(diagnostic);
+                        }
+                        break;
+                    }
+
+                    self.loop_depth = loop_depth;
+                    self.function_depth = function_depth;
+                    self.nesting_depth = nesting_depth;
+                    self.synchronize(statement_start, false);
+                }
+            }
+
+            self.next();
+        }
+
+        if self.diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.diagnostics))
+        }
+    }
+
+    /// Skips the rest of the statement starting at `statement_start` after a syntax error, so
+    /// parsing can continue with the next one. Leaves the parser at the ';' or '}' ending the
+    /// statement, or right before the next statement or the '}' of the enclosing block, if the
+    /// statement is `in_block`.
+    fn synchronize(&mut self, statement_start: usize, in_block: bool) {
+        // Braces opened by the statement and not closed yet, e.g. the block of a broken `if`
+        // or a table constructor, counting the ones parsed before the error
+        let mut brace_depth: usize = 0;
+        for token in &self.input_tokens[statement_start..self.current_token_index] {
+            match token.kind {
+                TokenKind::OBrace => brace_depth += 1,
+                TokenKind::CBrace => brace_depth = brace_depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+
+        loop {
+            match self.current_token().kind {
+                TokenKind::Semicolon if brace_depth == 0 => return,
+                TokenKind::OBrace => brace_depth += 1,
+
+                // The '}' of the enclosing block, which `parse_block` has to see
+                TokenKind::CBrace if brace_depth == 0 => {
+                    if in_block && self.current_token_index > statement_start {
+                        self.current_token_index -= 1;
+                    }
+                    return;
+                }
+
+                TokenKind::CBrace => {
+                    brace_depth -= 1;
+
+                    // The end of e.g. an `if` block, unless the statement goes on after it like
+                    // a table constructor followed by ';'
+                    if brace_depth == 0
+                        && self.peek().is_none_or(|next_token| {
+                            next_token.kind == TokenKind::Identifier
+                                || next_token.kind == TokenKind::CBrace
+                                || is_statement_start(&next_token.kind)
+                        })
+                    {
+                        return;
+                    }
+                }
 
-                    // Arguments
+                _ => {}
+            }
 
-                    let mut arguments = Vec::<Node>::new();
+            match self.peek() {
+                None => return,
+                Some(next_token)
+                    if brace_depth == 0
+                        && (next_token.kind == TokenKind::CBrace
+                            || is_statement_start(&next_token.kind)) =>
+                {
+                    return
+                }
+                Some(_) => self.next(),
+            }
+        }
+    }
 
-                    loop {
-                        let current_token = self.current_token();
+    fn parse_statement(&mut self) -> Result<Node, Box<Diagnostic>> {
+        let current_token = self.current_token();
+        Ok(match current_token.kind {
+            // let x = 1;
+            TokenKind::Let => {
+                self.expect_next(TokenKind::Identifier)?;
+                self.next();
+                let variable_name = self.current_token().value;
 
-                        match current_token.kind {
-                            TokenKind::CParen => break,
+                let value = self.parse_assigned_value()?;
 
-                            TokenKind::String => {
-                                let new_value = self.current_token().value.replace("\\n", "\n");
+                Node {
+                    location: current_token.location.to(&value.location),
+                    kind: NodeKind::Let(variable_name, Box::new(value)),
+                }
+            }
 
-                                arguments.push(Node {
-                                    kind: NodeKind::String(new_value),
-                                    location: current_token.location,
-                                });
-                            }
+            // if x > 1 { ... } elseif x > 0 { ... } else { ... }
+            TokenKind::If => {
+                let mut branches = Vec::new();
+                let mut else_block = None;
 
-                            TokenKind::Comma => {}
+                branches.push(self.parse_conditional_block()?);
 
-                            other => self
-                                .throw_err(format!("Unexpected token {:?} for arguments", other)),
+                while let Some(next_token) = self.peek() {
+                    match next_token.kind {
+                        TokenKind::Elseif => {
+                            self.next();
+                            branches.push(self.parse_conditional_block()?);
                         }
 
-                        self.expect_next_either(&NEXT_ARGUMENT_TOKENS);
-                        self.next();
+                        TokenKind::Else => {
+                            self.next();
+                            self.expect_next(TokenKind::OBrace)?;
+                            self.next();
+                            else_block = Some(Box::new(self.parse_block()?));
+                            break;
+                        }
+
+                        _ => break,
+                    }
+                }
+
+                Node {
+                    location: current_token.location.to(&self.current_token().location),
+                    kind: NodeKind::If(branches, else_block),
+                }
+            }
+
+            // while x < 10 { ... }
+            TokenKind::While => {
+                self.expect_next_token()?;
+                self.next();
+
+                let condition = self.parse_expression(0)?;
+
+                self.expect_next(TokenKind::OBrace)?;
+                self.next();
+
+                let block = self.parse_loop_block()?;
+
+                Node {
+                    location: current_token.location.to(&block.location),
+                    kind: NodeKind::While(Box::new(condition), Box::new(block)),
+                }
+            }
+
+            TokenKind::For => {
+                self.expect_next(TokenKind::Identifier)?;
+                self.next();
+
+                // for i = 1, 10, 2 { ... }
+                if self
+                    .peek()
+                    .is_some_and(|token| token.kind == TokenKind::Equal)
+                {
+                    let variable_name = self.current_token().value;
+                    self.next();
+
+                    let mut range = self.parse_expression_list(TokenKind::OBrace)?;
+                    if range.len() < 2 || range.len() > 3 {
+                        return Err(self.error(ErrorCode::InvalidNumericFor, format!(
+                            "Expected 2 or 3 expressions (start, stop[, step]) in numeric 'for', but found {}",
+                            range.len()
+                        )));
                     }
 
-                    self.expect_next(TokenKind::Semicolon);
+                    let step = if range.len() == 3 {
+                        range.pop().map(Box::new)
+                    } else {
+                        None
+                    };
+                    let stop = range.pop().unwrap();
+                    let start = range.pop().unwrap();
+
                     self.next();
+                    let block = self.parse_loop_block()?;
 
-                    self.output_nodes.push(Node {
-                        kind: NodeKind::FunctionCall(function_name, arguments),
-                        location: self.current_token().location,
+                    return Ok(Node {
+                        location: current_token.location.to(&block.location),
+                        kind: NodeKind::NumericFor(
+                            variable_name,
+                            Box::new(start),
+                            Box::new(stop),
+                            step,
+                            Box::new(block),
+                        ),
                     });
                 }
 
-                other => self.throw_err(format!("Unexpected token kind '{:?}'", other,)),
+                // for k, v in pairs(t) { ... }
+                let mut variable_names = vec![self.current_token().value];
+                while self
+                    .peek()
+                    .is_some_and(|token| token.kind == TokenKind::Comma)
+                {
+                    self.next();
+                    self.expect_next(TokenKind::Identifier)?;
+                    self.next();
+                    variable_names.push(self.current_token().value);
+                }
+
+                self.expect_next(TokenKind::In)?;
+                self.next();
+
+                let iterator = self.parse_expression_list(TokenKind::OBrace)?;
+
+                self.next();
+                let block = self.parse_loop_block()?;
+
+                Node {
+                    location: current_token.location.to(&block.location),
+                    kind: NodeKind::GenericFor(variable_names, iterator, Box::new(block)),
+                }
+            }
+
+            // fn add(a, b) { return a + b; }
+            TokenKind::Fn => {
+                self.expect_next(TokenKind::Identifier)?;
+                self.next();
+                let function_name = self.current_token().value;
+
+                let (parameters, block) = self.parse_function(&function_name)?;
+
+                Node {
+                    location: current_token.location.to(&block.location),
+                    kind: NodeKind::FunctionDefinition(function_name, parameters, Rc::new(block)),
+                }
+            }
+
+            // return;
+            // return a, b;
+            TokenKind::Return => {
+                if self.function_depth == 0 {
+                    return Err(self.error(
+                        ErrorCode::ReturnOutsideFunction,
+                        "'return' can only be used inside of a function",
+                    ));
+                }
+
+                let mut values = Vec::new();
+
+                if self
+                    .peek()
+                    .is_none_or(|token| token.kind != TokenKind::Semicolon)
+                {
+                    values = self.parse_expression_list(TokenKind::Semicolon)?;
+                }
+
+                self.next();
+
+                Node {
+                    location: current_token.location.to(&self.current_token().location),
+                    kind: NodeKind::Return(values),
+                }
+            }
+
+            TokenKind::Break | TokenKind::Continue => {
+                if self.loop_depth == 0 {
+                    return Err(self.error(
+                        ErrorCode::LoopControlOutsideLoop,
+                        format!(
+                            "'{}' can only be used inside of a loop",
+                            current_token.value
+                        ),
+                    ));
+                }
+
+                self.expect_next(TokenKind::Semicolon)?;
+                self.next();
+
+                Node {
+                    kind: if current_token.kind == TokenKind::Break {
+                        NodeKind::Break
+                    } else {
+                        NodeKind::Continue
+                    },
+                    location: current_token.location,
+                }
+            }
+
+            TokenKind::OBrace => self.parse_block()?,
+
+            TokenKind::Identifier | TokenKind::OParen => {
+                let expression = self.parse_expression(0)?;
+
+                // x = 1;
+                // t.x = 1;
+                if self
+                    .peek()
+                    .is_some_and(|token| token.kind == TokenKind::Equal)
+                {
+                    if !matches!(
+                        expression.kind,
+                        NodeKind::Identifier(_) | NodeKind::Index(..)
+                    ) {
+                        return Err(self.error(
+                            ErrorCode::InvalidAssignment,
+                            "Can only assign to variables and table fields",
+                        ));
+                    }
+
+                    let value = self.parse_assigned_value()?;
+
+                    return Ok(Node {
+                        location: current_token.location.to(&value.location),
+                        kind: NodeKind::Assign(Box::new(expression), Box::new(value)),
+                    });
+                }
+
+                // print();
+                if !matches!(
+                    expression.kind,
+                    NodeKind::FunctionCall(..) | NodeKind::MethodCall(..)
+                ) {
+                    let mut diagnostic = self.error(
+                        ErrorCode::ExpectedStatement,
+                        "Expected a function call or an assignment",
+                    );
+
+                    // A misspelled keyword is parsed as a variable, e.g. `retrun x;`
+                    if let NodeKind::Identifier(name) = &expression.kind {
+                        diagnostic
+                            .suggestions
+                            .extend(similar_name(name, STATEMENT_KEYWORDS));
+                    }
+
+                    return Err(diagnostic);
+                }
+
+                self.expect_next(TokenKind::Semicolon)?;
+                self.next();
+
+                expression
+            }
+
+            other => {
+                return Err(self.error(
+                    ErrorCode::UnexpectedToken,
+                    format!("Unexpected token kind '{:?}'", other,),
+                ))
+            }
+        })
+    }
+
+    /// Parses the statements between '{' and '}', starting at the '{' and leaving the parser at the '}'
+    fn parse_block(&mut self) -> Result<Node, Box<Diagnostic>> {
+        self.enter_nesting()?;
+
+        let open_brace_token = self.current_token();
+        let mut statements = Vec::new();
+
+        loop {
+            if self.peek().is_none() {
+                return Err(self.error(
+                    ErrorCode::UnexpectedEndOfFile,
+                    format!(
+                        "Missing '}}' for the block opened at line {}, column {}",
+                        open_brace_token.location.start_line, open_brace_token.location.start_col
+                    ),
+                ));
+            }
+            self.next();
+
+            if self.current_token().kind == TokenKind::CBrace {
+                break;
+            }
+
+            let (loop_depth, function_depth, nesting_depth) =
+                (self.loop_depth, self.function_depth, self.nesting_depth);
+            let statement_start = self.current_token_index;
+
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+
+                Err(_) if self.is_at_error_token() => {
+                    self.loop_depth = loop_depth;
+                    self.function_depth = function_depth;
+                    self.nesting_depth = nesting_depth;
+                    self.synchronize(statement_start, true);
+                }
+
+                // The last allowed error goes straight up to `parse`, which stops there
+                Err(diagnostic) if self.diagnostics.len() + 1 >= self.max_errors => {
+                    return Err(diagnostic)
+                }
+
+                Err(diagnostic) => {
+                    self.diagnostics.push(*diagnostic);
+
+                    self.loop_depth = loop_depth;
+                    self.function_depth = function_depth;
+                    self.nesting_depth = nesting_depth;
+                    self.synchronize(statement_start, true);
+                }
+            }
+        }
+
+        self.nesting_depth -= 1;
+
+        Ok(Node {
+            kind: NodeKind::Block(statements),
+            location: open_brace_token.location.to(&self.current_token().location),
+        })
+    }
+
+    /// Parses comma separated expressions, starting at the token before the first one.
+    /// Leaves the parser at the last token of the list, which must be followed by `terminator`.
+    fn parse_expression_list(
+        &mut self,
+        terminator: TokenKind,
+    ) -> Result<Vec<Node>, Box<Diagnostic>> {
+        let mut expressions = Vec::new();
+
+        loop {
+            self.expect_next_token()?;
+            self.next();
+
+            expressions.push(self.parse_expression(0)?);
+
+            self.expect_next_either(&[TokenKind::Comma, terminator.clone()])?;
+            if self.peek().unwrap().kind == terminator {
+                break;
+            }
+            self.next();
+        }
+
+        Ok(expressions)
+    }
+
+    /// Same as `parse_block`, but allows `break` and `continue` inside of it
+    fn parse_loop_block(&mut self) -> Result<Node, Box<Diagnostic>> {
+        self.loop_depth += 1;
+        let block = self.parse_block()?;
+        self.loop_depth -= 1;
+
+        Ok(block)
+    }
+
+    /// Parses the parameters and block of a function, starting at the token before the '('
+    /// and leaving the parser at the '}'
+    fn parse_function(
+        &mut self,
+        function_name: &str,
+    ) -> Result<(Vec<String>, Node), Box<Diagnostic>> {
+        self.expect_next(TokenKind::OParen)?;
+        self.next();
+
+        let mut parameters = Vec::<String>::new();
+
+        if self
+            .peek()
+            .is_none_or(|token| token.kind != TokenKind::CParen)
+        {
+            loop {
+                self.expect_next(TokenKind::Identifier)?;
+                self.next();
+
+                let parameter = self.current_token().value;
+                if parameters.contains(&parameter) {
+                    return Err(self.error(
+                        ErrorCode::DuplicateParameter,
+                        format!(
+                            "Duplicate parameter '{}' in function '{}'",
+                            parameter, function_name
+                        ),
+                    ));
+                }
+                parameters.push(parameter);
+
+                self.expect_next_either(&[TokenKind::Comma, TokenKind::CParen])?;
+                if self.peek().unwrap().kind == TokenKind::CParen {
+                    break;
+                }
+                self.next();
+            }
+        }
+
+        self.next();
+        self.expect_next(TokenKind::OBrace)?;
+        self.next();
+
+        Ok((parameters, self.parse_function_block()?))
+    }
+
+    /// Same as `parse_block`, but allows `return` inside of it and disallows `break` and
+    /// `continue` for loops outside of the function
+    fn parse_function_block(&mut self) -> Result<Node, Box<Diagnostic>> {
+        let loop_depth = self.loop_depth;
+        self.loop_depth = 0;
+        self.function_depth += 1;
+
+        let block = self.parse_block()?;
+
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+
+        Ok(block)
+    }
+
+    /// Parses `condition { ... }`, starting at the `if` or `elseif` before it
+    fn parse_conditional_block(&mut self) -> Result<(Node, Node), Box<Diagnostic>> {
+        self.expect_next_token()?;
+        self.next();
+
+        let condition = self.parse_expression(0)?;
+
+        self.expect_next(TokenKind::OBrace)?;
+        self.next();
+
+        Ok((condition, self.parse_block()?))
+    }
+
+    /// Parses the `= value;` part of a `let` or an assignment, starting at the token before '='
+    fn parse_assigned_value(&mut self) -> Result<Node, Box<Diagnostic>> {
+        self.expect_next(TokenKind::Equal)?;
+        self.next();
+        self.expect_next_token()?;
+        self.next();
+
+        let value = self.parse_expression(0)?;
+
+        self.expect_next(TokenKind::Semicolon)?;
+        self.next();
+
+        Ok(value)
+    }
+
+    /// Parses `(arguments...)`, starting at the token before the '(' and leaving the parser at
+    /// the ')'
+    fn parse_arguments(&mut self) -> Result<Vec<Node>, Box<Diagnostic>> {
+        self.expect_next(TokenKind::OParen)?;
+        self.next();
+
+        let mut arguments = Vec::<Node>::new();
+
+        if self
+            .peek()
+            .is_none_or(|token| token.kind != TokenKind::CParen)
+        {
+            arguments = self.parse_expression_list(TokenKind::CParen)?;
+        }
+
+        self.next();
+
+        Ok(arguments)
+    }
+
+    /// Parses an expression starting at the current token, leaving the parser at its last token.
+    /// Only binary operators binding at least as tight as `min_precedence` are consumed.
+    fn parse_expression(&mut self, min_precedence: u8) -> Result<Node, Box<Diagnostic>> {
+        self.enter_nesting()?;
+
+        let mut left = self.parse_unary()?;
+
+        while let Some(next_token) = self.peek() {
+            let operator = match binary_operator(&next_token.kind) {
+                Some(operator) => operator,
+                None => break,
+            };
+
+            if operator.precedence() < min_precedence {
+                break;
             }
 
             self.next();
+            self.expect_next_token()?;
+            self.next();
+
+            // All binary operators are left associative
+            let right = self.parse_expression(operator.precedence() + 1)?;
+
+            left = Node {
+                location: left.location.to(&right.location),
+                kind: NodeKind::BinaryOp(operator, Box::new(left), Box::new(right)),
+            };
         }
+
+        self.nesting_depth -= 1;
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, Box<Diagnostic>> {
+        let current_token = self.current_token();
+
+        let operator = match current_token.kind {
+            TokenKind::Minus => UnaryOperator::Negate,
+            TokenKind::Not => UnaryOperator::Not,
+            TokenKind::Hash => UnaryOperator::Length,
+            _ => return self.parse_postfix(),
+        };
+
+        self.expect_next_token()?;
+        self.next();
+
+        self.enter_nesting()?;
+        let operand = self.parse_unary()?;
+        self.nesting_depth -= 1;
+
+        Ok(Node {
+            location: current_token.location.to(&operand.location),
+            kind: NodeKind::UnaryOp(operator, Box::new(operand)),
+        })
+    }
+
+    /// Parses a primary expression followed by any amount of indexing and calls,
+    /// e.g. `t.a[1]`, `string.upper(s)` or `object:method()`
+    fn parse_postfix(&mut self) -> Result<Node, Box<Diagnostic>> {
+        let mut expression = self.parse_primary()?;
+
+        while let Some(next_token) = self.peek() {
+            let key = match next_token.kind {
+                // f(arguments)
+                TokenKind::OParen => {
+                    let arguments = self.parse_arguments()?;
+
+                    expression = Node {
+                        location: expression.location.to(&self.current_token().location),
+                        kind: NodeKind::FunctionCall(Box::new(expression), arguments),
+                    };
+                    continue;
+                }
+
+                // object:method(arguments)
+                TokenKind::Colon => {
+                    self.next();
+                    self.expect_next(TokenKind::Identifier)?;
+                    self.next();
+
+                    let method_name = self.current_token().value;
+                    let arguments = self.parse_arguments()?;
+
+                    expression = Node {
+                        location: expression.location.to(&self.current_token().location),
+                        kind: NodeKind::MethodCall(Box::new(expression), method_name, arguments),
+                    };
+                    continue;
+                }
+
+                // t.name
+                TokenKind::Dot => {
+                    self.next();
+                    self.expect_next(TokenKind::Identifier)?;
+                    self.next();
+
+                    let name_token = self.current_token();
+                    Node {
+                        kind: NodeKind::String(name_token.value),
+                        location: name_token.location,
+                    }
+                }
+
+                // t[key]
+                TokenKind::OBracket => {
+                    self.next();
+                    self.expect_next_token()?;
+                    self.next();
+
+                    let key = self.parse_expression(0)?;
+
+                    self.expect_next(TokenKind::CBracket)?;
+                    self.next();
+
+                    key
+                }
+
+                _ => break,
+            };
+
+            expression = Node {
+                location: expression.location.to(&self.current_token().location),
+                kind: NodeKind::Index(Box::new(expression), Box::new(key)),
+            };
+        }
+
+        Ok(expression)
+    }
+
+    /// Parses `{ 1, 2, name = "x", [key] = value }`, starting at the '{' and leaving the parser
+    /// at the '}'
+    fn parse_table(&mut self) -> Result<Node, Box<Diagnostic>> {
+        let open_brace_token = self.current_token();
+        let mut entries = Vec::new();
+
+        loop {
+            self.expect_next_token()?;
+            self.next();
+
+            let current_token = self.current_token();
+
+            let key = match current_token.kind {
+                TokenKind::CBrace => break,
+
+                // name = value
+                TokenKind::Identifier
+                    if self
+                        .peek()
+                        .is_some_and(|token| token.kind == TokenKind::Equal) =>
+                {
+                    self.next();
+
+                    Some(Node {
+                        kind: NodeKind::String(current_token.value),
+                        location: current_token.location,
+                    })
+                }
+
+                // [key] = value
+                TokenKind::OBracket => {
+                    self.expect_next_token()?;
+                    self.next();
+
+                    let key = self.parse_expression(0)?;
+
+                    self.expect_next(TokenKind::CBracket)?;
+                    self.next();
+                    self.expect_next(TokenKind::Equal)?;
+                    self.next();
+
+                    Some(key)
+                }
+
+                _ => None,
+            };
+
+            // Move onto the value, unless already on it for positional entries
+            if key.is_some() {
+                self.expect_next_token()?;
+                self.next();
+            }
+
+            let value = self.parse_expression(0)?;
+            entries.push((key, value));
+
+            self.expect_next_either(&[TokenKind::Comma, TokenKind::CBrace])?;
+            if self.peek().unwrap().kind == TokenKind::Comma {
+                self.next();
+            }
+        }
+
+        Ok(Node {
+            kind: NodeKind::Table(entries),
+            location: open_brace_token.location.to(&self.current_token().location),
+        })
+    }
+
+    /// Parses "a${x}b${y}c" from its InterpolationStart token to its InterpolationEnd token
+    fn parse_interpolation(&mut self) -> Result<Node, Box<Diagnostic>> {
+        let start_token = self.current_token();
+        let mut parts = Vec::<Node>::new();
+        let mut string_token = start_token.clone();
+
+        loop {
+            if !string_token.value.is_empty() {
+                parts.push(Node {
+                    kind: NodeKind::String(string_token.value.clone()),
+                    location: string_token.location.clone(),
+                });
+            }
+
+            if string_token.kind == TokenKind::InterpolationEnd {
+                break;
+            }
+
+            self.expect_next_token()?;
+            if matches!(
+                self.peek().unwrap().kind,
+                TokenKind::InterpolationMiddle | TokenKind::InterpolationEnd
+            ) {
+                return Err(self.error(
+                    ErrorCode::EmptyInterpolation,
+                    "Missing expression inside of '${}' in string",
+                ));
+            }
+            self.next();
+
+            parts.push(self.parse_expression(0)?);
+
+            self.expect_next_either(&[
+                TokenKind::InterpolationMiddle,
+                TokenKind::InterpolationEnd,
+            ])?;
+            self.next();
+            string_token = self.current_token();
+        }
+
+        Ok(Node {
+            kind: NodeKind::Interpolation(parts),
+            location: start_token.location.to(&string_token.location),
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, Box<Diagnostic>> {
+        let current_token = self.current_token();
+
+        let kind = match current_token.kind {
+            TokenKind::String => NodeKind::String(current_token.value.clone()),
+            TokenKind::Number => NodeKind::Number(current_token.value.parse::<f64>().unwrap()),
+            TokenKind::Nil => NodeKind::Nil,
+            TokenKind::True => NodeKind::Boolean(true),
+            TokenKind::False => NodeKind::Boolean(false),
+
+            // Anonymous function, e.g. let add = fn(a, b) { return a + b; };
+            TokenKind::Fn => {
+                let (parameters, block) = self.parse_function("anonymous")?;
+
+                return Ok(Node {
+                    location: current_token.location.to(&block.location),
+                    kind: NodeKind::Function(parameters, Rc::new(block)),
+                });
+            }
+            TokenKind::Identifier => NodeKind::Identifier(current_token.value),
+
+            TokenKind::OBrace => return self.parse_table(),
+            TokenKind::InterpolationStart => return self.parse_interpolation(),
+
+            // Grouping, e.g. (1 + 2) * 3
+            TokenKind::OParen => {
+                self.expect_next_token()?;
+                self.next();
+
+                let expression = self.parse_expression(0)?;
+
+                self.expect_next(TokenKind::CParen)?;
+                self.next();
+
+                return Ok(expression);
+            }
+
+            other => {
+                return Err(self.error(
+                    ErrorCode::UnexpectedToken,
+                    format!(
+                        "Unexpected token '{}' of kind '{:?}', expected an expression",
+                        current_token.value, other
+                    ),
+                ))
+            }
+        };
+
+        Ok(Node {
+            kind,
+            location: current_token.location,
+        })
+    }
+
+    /// Goes one level deeper into nested expressions or blocks, the caller goes back up
+    fn enter_nesting(&mut self) -> Result<(), Box<Diagnostic>> {
+        if self.nesting_depth >= MAX_NESTING_DEPTH {
+            return Err(self.error(
+                ErrorCode::NestingTooDeep,
+                format!(
+                    "Expressions and blocks are nested too deeply, more than {} levels",
+                    MAX_NESTING_DEPTH
+                ),
+            ));
+        }
+
+        self.nesting_depth += 1;
+        Ok(())
     }
 
     fn next(&mut self) {
@@ -97,50 +967,78 @@ impl Parser {
         Some(self.input_tokens[self.current_token_index + 1].clone())
     }
 
-    fn expect_next(&mut self, expected_kind: TokenKind) {
+    fn expect_next(&self, expected_kind: TokenKind) -> Result<(), Box<Diagnostic>> {
         let next_token = self.peek();
         let current_token = self.current_token();
 
         if next_token.is_none() {
-            self.throw_err(format!(
-                "Expected token after '{}' to be of kind '{:?}', but is end of file.",
-                current_token.value, expected_kind
+            return Err(self.error(
+                ErrorCode::UnexpectedEndOfFile,
+                format!(
+                    "Expected token after '{}' to be of kind '{:?}', but is end of file.",
+                    current_token.value, expected_kind
+                ),
             ));
         }
 
         let next_token = next_token.unwrap();
 
         if next_token.kind != expected_kind {
-            self.input_tokens[self.current_token_index]
-                .location
-                .start_col = self.input_tokens[self.current_token_index].location.end_col;
+            // Point at the end of the current token, where the expected token is missing
+            let mut location = current_token.location.clone();
+            location.start_col = location.end_col;
+
+            return Err(Box::new(Diagnostic::error(
+                ErrorCode::UnexpectedToken,
+                format!(
+                    "Expected token after '{}' to be of kind '{:?}', but found '{}' which is of kind '{:?}'",
+                    current_token.value, expected_kind, next_token.value, next_token.kind
+                ),
+                location,
+            )));
+        }
+
+        Ok(())
+    }
 
-            self.throw_err(format!(
-                "Expected token after '{}' to be of kind '{:?}', but found '{}' which is of kind '{:?}'",
-                current_token.value, expected_kind, next_token.value, next_token.kind
+    fn expect_next_token(&self) -> Result<(), Box<Diagnostic>> {
+        if self.peek().is_none() {
+            return Err(self.error(
+                ErrorCode::UnexpectedEndOfFile,
+                format!(
+                    "Expected token after '{}', but is end of file.",
+                    self.current_token().value
+                ),
             ));
         }
+
+        Ok(())
     }
 
-    fn expect_next_either(&mut self, expected_kinds: &[TokenKind]) {
+    fn expect_next_either(&self, expected_kinds: &[TokenKind]) -> Result<(), Box<Diagnostic>> {
         let next_token = self.peek();
         let current_token = self.current_token();
 
         if next_token.is_none() {
-            self.throw_err(format!(
-                "Expected token after '{}' to be either of kinds '{:?}', but is end of file.",
-                current_token.value, expected_kinds
+            return Err(self.error(
+                ErrorCode::UnexpectedEndOfFile,
+                format!(
+                    "Expected token after '{}' to be either of kinds '{:?}', but is end of file.",
+                    current_token.value, expected_kinds
+                ),
             ));
         }
 
         let next_token = next_token.unwrap();
 
         if !expected_kinds.contains(&next_token.kind) {
-            self.throw_err(format!(
+            return Err(self.error(ErrorCode::UnexpectedToken, format!(
                 "Expected token after '{}' to be either of kinds '{:?}', but found '{}' which is of kind '{:?}'.",
                 current_token.value, expected_kinds, next_token.value, next_token.kind
-            ));
+            )));
         }
+
+        Ok(())
     }
 
     #[inline]
@@ -148,38 +1046,64 @@ impl Parser {
         self.input_tokens[self.current_token_index].clone()
     }
 
+    /// Whether the current or the next token is invalid input, which the lexer has already
+    /// reported, so the syntax error it caused is not reported again
+    fn is_at_error_token(&self) -> bool {
+        self.current_token().kind == TokenKind::Error
+            || self
+                .peek()
+                .is_some_and(|next_token| next_token.kind == TokenKind::Error)
+    }
+
     #[inline]
     fn is_not_last_token(&self) -> bool {
         self.current_token_index < self.input_tokens_length
     }
 
-    fn throw_err<T: Into<String>>(&self, msg: T) {
-        let current_token_location = self.current_token().location;
-        let start_line_number = current_token_location.start_line;
-        // let end_line_number = current_token_location.end_line;
+    /// Diagnostic pointing at the current token
+    fn error<T: Into<String>>(&self, code: ErrorCode, msg: T) -> Box<Diagnostic> {
+        Box::new(Diagnostic::error(code, msg, self.current_token().location))
+    }
+}
 
-        let start_col_number = current_token_location.start_col;
-        let end_col_number = current_token_location.end_col;
+// Keywords starting a statement or a part of one, suggested for misspelled keywords
+const STATEMENT_KEYWORDS: [&str; 10] = [
+    "let", "if", "elseif", "else", "while", "for", "fn", "return", "break", "continue",
+];
+
+/// Whether a statement can start with `kind`, used to find where to continue after an error
+fn is_statement_start(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Let
+            | TokenKind::If
+            | TokenKind::While
+            | TokenKind::For
+            | TokenKind::Fn
+            | TokenKind::Return
+            | TokenKind::Break
+            | TokenKind::Continue
+    )
+}
 
-        let line_number_spaces = " ".repeat(start_line_number.to_string().len());
+fn binary_operator(kind: &TokenKind) -> Option<BinaryOperator> {
+    match kind {
+        TokenKind::And => Some(BinaryOperator::And),
+        TokenKind::Or => Some(BinaryOperator::Or),
 
-        // Assuming for now that start_line_number == end_line_number
+        TokenKind::Plus => Some(BinaryOperator::Add),
+        TokenKind::Minus => Some(BinaryOperator::Subtract),
+        TokenKind::Star => Some(BinaryOperator::Multiply),
+        TokenKind::Slash => Some(BinaryOperator::Divide),
+        TokenKind::Percent => Some(BinaryOperator::Modulo),
 
-        println!("[Error]");
-        println!("{}\n", msg.into());
-        println!(
-            "[Location] {}:{}:{}",
-            current_token_location.file_path, start_line_number, start_col_number
-        );
-        println!(" {} |", line_number_spaces);
-        println!(" {} | {}", start_line_number, current_token_location.line,);
-        println!(
-            " {} |{}{}",
-            line_number_spaces,
-            " ".repeat(start_col_number),
-            "^".repeat(end_col_number - start_col_number + 1)
-        );
+        TokenKind::EqualEqual => Some(BinaryOperator::Equal),
+        TokenKind::BangEqual => Some(BinaryOperator::NotEqual),
+        TokenKind::Less => Some(BinaryOperator::Less),
+        TokenKind::LessEqual => Some(BinaryOperator::LessEqual),
+        TokenKind::Greater => Some(BinaryOperator::Greater),
+        TokenKind::GreaterEqual => Some(BinaryOperator::GreaterEqual),
 
-        std::process::exit(1);
+        _ => None,
     }
 }
@@ -0,0 +1,252 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use crate::value::{Function, Value};
+
+/// Lua-style table, keys 1..=n live in the array part, every other key in the hash part
+#[derive(Default)]
+pub struct Table {
+    array: Vec<Value>,
+    // Hash part entries in insertion order, keeping `next` stable while iterating.
+    // Entries set to nil stay around so iteration can continue from them, until adding a key
+    // would grow the part and most of it is nil.
+    hash_entries: Vec<(Value, Value)>,
+    hash_indices: HashMap<TableKey, usize>,
+
+    pub metatable: Option<Rc<RefCell<Table>>>,
+}
+
+impl Table {
+    pub fn get(&self, key: &Value) -> Value {
+        if let Some(index) = self.array_index(key) {
+            if index < self.array.len() {
+                return self.array[index].clone();
+            }
+        }
+
+        match self.hash_indices.get(&TableKey(key.clone())) {
+            Some(&index) => self.hash_entries[index].1.clone(),
+            None => Value::Nil,
+        }
+    }
+
+    /// Sets `key` to `value`, the key must neither be nil nor NaN
+    pub fn set(&mut self, key: Value, value: Value) {
+        if let Some(index) = self.array_index(&key) {
+            // Nils are kept in the array part, so `next` can continue from cleared keys
+            if index < self.array.len() {
+                self.array[index] = value;
+                return;
+            }
+
+            if index == self.array.len() && value != Value::Nil {
+                self.array.push(value);
+                self.hash_set(key, Value::Nil);
+                self.migrate_to_array();
+
+                return;
+            }
+        }
+
+        self.hash_set(key, value);
+    }
+
+    /// Returns a border like Lua's `#`, an index whose value is non-nil and followed by nil,
+    /// or 0 if the first value is nil
+    pub fn length(&self) -> usize {
+        if self.array.last().is_none_or(|value| *value != Value::Nil) {
+            return self.array.len();
+        }
+
+        // Binary search, `low` is always a border candidate and the value at `high` is nil
+        let mut low = 0;
+        let mut high = self.array.len();
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+            if self.array[middle - 1] == Value::Nil {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+
+        low
+    }
+
+    /// Returns the entry after `key`, or the first entry if `key` is nil.
+    /// Returns `None` if `key` is not in the table.
+    pub fn next(&self, key: &Value) -> Option<Option<(Value, Value)>> {
+        // Position in the array part followed by the hash part to continue searching from
+        let position = match key {
+            Value::Nil => 0,
+
+            _ => match self.array_index(key) {
+                Some(index) if index < self.array.len() => index + 1,
+
+                _ => match self.hash_indices.get(&TableKey(key.clone())) {
+                    Some(&index) => self.array.len() + index + 1,
+                    None => return None,
+                },
+            },
+        };
+
+        for index in position..self.array.len() {
+            if self.array[index] != Value::Nil {
+                return Some(Some((
+                    Value::Number((index + 1) as f64),
+                    self.array[index].clone(),
+                )));
+            }
+        }
+
+        let hash_position = position.saturating_sub(self.array.len());
+        Some(
+            self.hash_entries
+                .iter()
+                .skip(hash_position)
+                .find(|(_, value)| *value != Value::Nil)
+                .cloned(),
+        )
+    }
+
+    /// Returns the array part index for integer keys up to one past the end of the array part
+    fn array_index(&self, key: &Value) -> Option<usize> {
+        match key {
+            Value::Number(number)
+                if number.fract() == 0.0
+                    && *number >= 1.0
+                    && *number <= (self.array.len() + 1) as f64 =>
+            {
+                Some(*number as usize - 1)
+            }
+
+            _ => None,
+        }
+    }
+
+    fn hash_set(&mut self, key: Value, value: Value) {
+        let table_key = TableKey(key.clone());
+
+        match self.hash_indices.get(&table_key) {
+            Some(&index) => self.hash_entries[index].1 = value,
+
+            None => {
+                if value != Value::Nil {
+                    if self.hash_entries.len() == self.hash_entries.capacity() {
+                        self.compact_hash();
+                    }
+
+                    self.hash_indices.insert(table_key, self.hash_entries.len());
+                    self.hash_entries.push((key, value));
+                }
+            }
+        }
+    }
+
+    /// Removes the nil entries of the hash part if they make up more than half of it. Like in
+    /// Lua, `next` can't continue from a cleared key after a new key was added.
+    fn compact_hash(&mut self) {
+        let nil_entries = self
+            .hash_entries
+            .iter()
+            .filter(|(_, value)| *value == Value::Nil)
+            .count();
+        if nil_entries * 2 <= self.hash_entries.len() {
+            return;
+        }
+
+        self.hash_entries.retain(|(_, value)| *value != Value::Nil);
+        self.hash_indices = self
+            .hash_entries
+            .iter()
+            .enumerate()
+            .map(|(index, (key, _))| (TableKey(key.clone()), index))
+            .collect();
+    }
+
+    /// Moves the keys following the array part from the hash part into it
+    fn migrate_to_array(&mut self) {
+        loop {
+            let key = Value::Number((self.array.len() + 1) as f64);
+
+            match self.hash_indices.get(&TableKey(key)) {
+                Some(&index) if self.hash_entries[index].1 != Value::Nil => {
+                    let value = std::mem::replace(&mut self.hash_entries[index].1, Value::Nil);
+                    self.array.push(value);
+                }
+
+                _ => break,
+            }
+        }
+    }
+}
+
+impl Drop for Table {
+    // Drops the tables inside this one in a loop rather than recursively, so a long chain
+    // of nested tables doesn't overflow the stack
+    fn drop(&mut self) {
+        let mut values = std::mem::take(&mut self.array);
+        take_contents(self, &mut values);
+
+        while let Some(value) = values.pop() {
+            if let Value::Table(table) = value {
+                // Tables still referenced elsewhere aren't dropped yet
+                if let Ok(table) = Rc::try_unwrap(table) {
+                    let mut table = table.into_inner();
+                    values.append(&mut table.array);
+                    take_contents(&mut table, &mut values);
+                }
+            }
+        }
+    }
+}
+
+/// Moves the values of the hash part and the metatable out of `table`, besides its array part
+fn take_contents(table: &mut Table, values: &mut Vec<Value>) {
+    for (key, value) in table.hash_entries.drain(..) {
+        values.push(key);
+        values.push(value);
+    }
+    values.extend(table.hash_indices.drain().map(|(key, _)| key.0));
+    values.extend(table.metatable.take().map(Value::Table));
+}
+
+impl fmt::Debug for Table {
+    // The contents are left out, a table may contain itself
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "table: {:p}", self)
+    }
+}
+
+/// A hashable table key, equal keys are equal values and tables and functions are hashed by
+/// identity
+#[derive(Debug, Clone)]
+struct TableKey(Value);
+
+impl PartialEq for TableKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for TableKey {}
+
+impl Hash for TableKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.0 {
+            Value::Nil => {}
+            Value::Boolean(boolean) => boolean.hash(state),
+            // 0.0 and -0.0 are equal, so they must hash the same
+            Value::Number(number) => (number + 0.0).to_bits().hash(state),
+            Value::String(string) => string.hash(state),
+            Value::Function(Function::Builtin(name)) => name.hash(state),
+            Value::Function(Function::User(function, _)) => Rc::as_ptr(function).hash(state),
+            Value::Table(table) => Rc::as_ptr(table).hash(state),
+        }
+    }
+}
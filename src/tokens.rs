@@ -2,12 +2,92 @@
 pub enum TokenKind {
     Identifier,
     String,
+    Number,
+
+    // Parts of an interpolated string "a${x}b${y}c", the expressions are lexed in between
+    InterpolationStart,  // "a${
+    InterpolationMiddle, // }b${
+    InterpolationEnd,    // }c"
+
+    // `/// text`, kept for tooling but skipped by the parser
+    DocComment,
+
+    // Invalid input, e.g. an unexpected character or an unterminated string. The lexer has
+    // already reported it, so the parser does not report errors caused by it again.
+    Error,
+
+    // Keywords
+    Nil,
+    True,
+    False,
+    Let,
+    If,
+    Elseif,
+    Else,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Fn,
+    Return,
+    And,
+    Or,
+    Not,
 
     OParen,
     CParen,
+    OBrace,
+    CBrace,
+    OBracket,
+    CBracket,
 
     Comma,
     Semicolon,
+    Equal,
+    Dot,
+    Colon,
+
+    // Operators
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Hash,
+
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl TokenKind {
+    /// Returns the kind of keyword `identifier` is, if it is one
+    pub fn keyword(identifier: &str) -> Option<TokenKind> {
+        match identifier {
+            "nil" => Some(TokenKind::Nil),
+            "true" => Some(TokenKind::True),
+            "false" => Some(TokenKind::False),
+            "let" => Some(TokenKind::Let),
+            "if" => Some(TokenKind::If),
+            "elseif" => Some(TokenKind::Elseif),
+            "else" => Some(TokenKind::Else),
+            "while" => Some(TokenKind::While),
+            "for" => Some(TokenKind::For),
+            "in" => Some(TokenKind::In),
+            "break" => Some(TokenKind::Break),
+            "continue" => Some(TokenKind::Continue),
+            "fn" => Some(TokenKind::Fn),
+            "return" => Some(TokenKind::Return),
+            "and" => Some(TokenKind::And),
+            "or" => Some(TokenKind::Or),
+            "not" => Some(TokenKind::Not),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -28,3 +108,19 @@ pub struct Location {
     pub file_path: String,
     pub line: String,
 }
+
+impl Location {
+    /// Returns a location spanning from the start of `self` to the end of `end`
+    pub fn to(&self, end: &Location) -> Location {
+        Location {
+            start_col: self.start_col,
+            start_line: self.start_line,
+
+            end_col: end.end_col,
+            end_line: end.end_line,
+
+            file_path: self.file_path.clone(),
+            line: self.line.clone(),
+        }
+    }
+}
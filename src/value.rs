@@ -0,0 +1,129 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::{Rc, Weak},
+};
+
+use crate::{ast::Node, environment::Environment, table::Table, tokens::Location};
+
+/// A runtime value produced by evaluating an expression
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Function(Function),
+    Table(Rc<RefCell<Table>>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Function {
+    // Builtin -> Name
+    Builtin(&'static str),
+    // User -> Function, its closure which is `None` only while stored in the closure itself
+    User(Rc<UserFunction>, Option<Rc<RefCell<Environment>>>),
+}
+
+/// A function defined in a script with `fn`
+pub struct UserFunction {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub block: Rc<Node>,
+    // The environment the function was created in, which it can keep using after it is gone.
+    // Each copy of the function keeps it alive rather than this, except the copy stored in the
+    // environment itself, which would be a reference cycle that is never freed.
+    pub closure: Weak<RefCell<Environment>>,
+    // How many variables were declared in the closure when the function was created
+    pub declarations: usize,
+    pub location: Location,
+}
+
+impl fmt::Debug for UserFunction {
+    // The closure is left out, it may contain this very function
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UserFunction")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .field("location", &self.location)
+            .finish()
+    }
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        match self {
+            Function::Builtin(name) => name,
+            Function::User(function, _) => &function.name,
+        }
+    }
+}
+
+impl PartialEq for Function {
+    /// Functions are only equal to themselves
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Function::Builtin(left), Function::Builtin(right)) => left == right,
+            (Function::User(left, _), Function::User(right, _)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// Name of the value's type, as returned by the `type` builtin
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Boolean(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Function(_) => "function",
+            Value::Table(_) => "table",
+        }
+    }
+
+    /// Like Lua, only `nil` and `false` are falsy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Boolean(left), Value::Boolean(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Function(left), Value::Function(right)) => left == right,
+            (Value::Table(left), Value::Table(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::Number(number) => write!(f, "{}", format_number(*number)),
+            Value::String(string) => write!(f, "{}", string),
+            Value::Function(Function::Builtin(name)) => write!(f, "builtin: {}", name),
+            Value::Function(Function::User(function, _)) => {
+                write!(f, "function: {}", function.name)
+            }
+            Value::Table(table) => write!(f, "table: {:p}", Rc::as_ptr(table)),
+        }
+    }
+}
+
+/// Formats a number the way Lua does, integral values are printed without a fraction
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e16 {
+        format!("{}", number as i64)
+    } else {
+        number.to_string()
+    }
+}
@@ -0,0 +1,418 @@
+use std::thread;
+
+use minute_lang::{
+    diagnostic::{Diagnostic, Renderer},
+    error_code::ErrorCode,
+    interpreter::Interpreter,
+    lexer::Lexer,
+    parser::Parser,
+    tokens::TokenKind,
+    value::Value,
+};
+
+fn lex(source_code: &str) -> Lexer {
+    Lexer::new(String::from(source_code), String::from("test.min"))
+}
+
+fn parse(source_code: &str) -> Result<Parser, Vec<Diagnostic>> {
+    let mut lexer = lex(source_code);
+    lexer.lex()?;
+
+    let mut parser = Parser::new(lexer.output_tokens);
+    parser.parse()?;
+    Ok(parser)
+}
+
+fn run(source_code: &str) -> Result<(), Box<Diagnostic>> {
+    let parser = parse(source_code).expect("source code should parse");
+    Interpreter::new(parser.output_nodes).interpret()
+}
+
+/// Runs the source code and returns the value of its variable `variable_name`
+fn run_and_get(source_code: &str, variable_name: &str) -> Value {
+    let parser = parse(source_code).expect("source code should parse");
+    let mut interpreter = Interpreter::new(parser.output_nodes);
+    interpreter.interpret().expect("source code should run");
+
+    interpreter
+        .get_variable(variable_name)
+        .expect("variable should be declared")
+}
+
+/// The numbers in the array part of a table
+fn numbers(table: Value) -> Vec<f64> {
+    let Value::Table(table) = table else {
+        panic!("expected a table, found {}", table);
+    };
+    let table = table.borrow();
+
+    (1..=table.length())
+        .map(|index| match table.get(&Value::Number(index as f64)) {
+            Value::Number(number) => number,
+            other => panic!("expected a number, found {}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn valid_program_runs() {
+    assert!(run("fn add(a, b) { return a + b; } let x = add(1, 2);").is_ok());
+}
+
+#[test]
+fn lexer_reports_every_invalid_character() {
+    let mut lexer = lex("let x = 1 @ 2;\nlet y = $;");
+    let diagnostics = lexer.lex().unwrap_err();
+
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.code == ErrorCode::UnexpectedCharacter));
+    assert_eq!(
+        (
+            diagnostics[0].location.start_line,
+            diagnostics[0].location.start_col
+        ),
+        (1, 11)
+    );
+    assert_eq!(
+        (
+            diagnostics[1].location.start_line,
+            diagnostics[1].location.start_col
+        ),
+        (2, 9)
+    );
+
+    // The rest of the file is still lexed
+    let error_tokens = lexer
+        .output_tokens
+        .iter()
+        .filter(|token| token.kind == TokenKind::Error)
+        .count();
+    assert_eq!(error_tokens, 2);
+    assert_eq!(
+        lexer.output_tokens.last().unwrap().kind,
+        TokenKind::Semicolon
+    );
+}
+
+#[test]
+fn parser_recovers_and_reports_every_syntax_error() {
+    let diagnostics = parse("let = 1;\nprint(1);\nreturn 2;").err().unwrap();
+
+    let codes = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.code)
+        .collect::<Vec<ErrorCode>>();
+    assert_eq!(
+        codes,
+        vec![ErrorCode::UnexpectedToken, ErrorCode::ReturnOutsideFunction]
+    );
+}
+
+#[test]
+fn parser_recovers_inside_of_braces() {
+    for source_code in [
+        "let t = { a = , b = 2 };\nprint(1);",
+        "let t = { a = { b = , c = 1 } };\nprint(1);",
+    ] {
+        let diagnostics = parse(source_code).err().unwrap();
+
+        assert_eq!(diagnostics.len(), 1, "{}", source_code);
+        assert_eq!(diagnostics[0].code, ErrorCode::UnexpectedToken);
+    }
+}
+
+#[test]
+fn parser_recovers_before_the_end_of_the_block() {
+    for source_code in [
+        "if x { let y = }\nprint(\"after\");",
+        "if x { let y = 1 + }\nprint(\"after\");",
+    ] {
+        let diagnostics = parse(source_code).err().unwrap();
+
+        assert_eq!(diagnostics.len(), 1, "{}", source_code);
+        assert_eq!(diagnostics[0].code, ErrorCode::UnexpectedToken);
+    }
+}
+
+#[test]
+fn deeply_nested_code_is_an_error() {
+    // Parsing up to the limit needs more stack than test threads have, like `main` gives it
+    let parse_deeply_nested = || {
+        for source_code in [
+            format!("print({}1{});", "(".repeat(3000), ")".repeat(3000)),
+            format!("print({}1);", "-".repeat(3000)),
+            format!("{}{}", "if true { ".repeat(3000), "}".repeat(3000)),
+        ] {
+            let diagnostics = parse(&source_code).err().unwrap();
+
+            assert_eq!(diagnostics[0].code, ErrorCode::NestingTooDeep);
+        }
+
+        let source_code = format!("print({}1{});", "(".repeat(100), ")".repeat(100));
+        assert!(parse(&source_code).is_ok());
+    };
+
+    thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(parse_deeply_nested)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn runtime_error_is_returned() {
+    let diagnostic = run("let x = 1;\nprint(x + nil);").unwrap_err();
+
+    assert_eq!(diagnostic.code, ErrorCode::InvalidOperand);
+    assert_eq!(diagnostic.location.start_line, 2);
+}
+
+#[test]
+fn runtime_error_suggests_similar_names() {
+    let diagnostic = run("pritn(1);").unwrap_err();
+
+    assert_eq!(diagnostic.code, ErrorCode::InvalidFunction);
+    assert_eq!(diagnostic.suggestions, vec![String::from("print")]);
+}
+
+#[test]
+fn call_metamethod_loop_is_an_error() {
+    let diagnostic = run("let t = {}; setmetatable(t, { __call = t }); t();").unwrap_err();
+
+    assert_eq!(diagnostic.code, ErrorCode::MetamethodLoop);
+}
+
+#[test]
+fn identifier_at_end_of_file_is_lexed() {
+    for (source_code, identifier) in [("print(1); foo", "foo"), ("é", "é")] {
+        let mut lexer = lex(source_code);
+        lexer.lex().unwrap();
+
+        let last_token = lexer.output_tokens.last().unwrap();
+        assert_eq!(last_token.kind, TokenKind::Identifier);
+        assert_eq!(last_token.value, identifier);
+    }
+}
+
+#[test]
+fn huge_string_repeat_is_an_error() {
+    for count in ["1e10", "1e19"] {
+        let diagnostic = run(&format!("let s = (\"ab\"):rep({});", count)).unwrap_err();
+
+        assert_eq!(diagnostic.code, ErrorCode::InvalidArgument);
+    }
+}
+
+#[test]
+fn redeclared_variable_does_not_change_captured_one() {
+    let source_code = "
+        let x = 1;
+        let f = fn() { return x; };
+        let x = 2;
+        let result = { f(), x };
+    ";
+
+    assert_eq!(numbers(run_and_get(source_code, "result")), vec![1.0, 2.0]);
+}
+
+#[test]
+fn redeclared_variable_does_not_hide_later_ones() {
+    let source_code = "
+        let x = 1;
+        fn a() { return b(); }
+        let x = 2;
+        fn b() { return 1; }
+        let result = { a() };
+    ";
+
+    assert_eq!(numbers(run_and_get(source_code, "result")), vec![1.0]);
+}
+
+/// The number in a field of a diagnostic rendered as JSON
+fn json_number(json: &str, field: &str) -> usize {
+    let start = json.find(&format!("\"{}\":", field)).unwrap() + field.len() + 3;
+    let length = json[start..].find(|c: char| !c.is_ascii_digit()).unwrap();
+
+    json[start..start + length].parse().unwrap()
+}
+
+/// The source code the byte offsets of a diagnostic rendered as JSON point at
+fn json_span_text<'a>(source_code: &'a str, json: &str) -> &'a str {
+    &source_code[json_number(json, "byte_start")..json_number(json, "byte_end")]
+}
+
+#[test]
+fn json_span_on_one_line() {
+    let source_code = "let x = 1;\nprint(x + nil);";
+    let json = Renderer::new(source_code).render_json(&run(source_code).unwrap_err());
+
+    assert_eq!(json_span_text(source_code, &json), "x + nil");
+    assert_eq!(
+        (
+            json_number(&json, "line_start"),
+            json_number(&json, "column_start"),
+            json_number(&json, "line_end"),
+            json_number(&json, "column_end")
+        ),
+        (2, 7, 2, 13)
+    );
+}
+
+#[test]
+fn json_span_across_lines() {
+    for source_code in [
+        "let s = \"abc\nd\" + 1;",
+        "let s = \"abcdefghijklmnop\nd\" + 1;",
+    ] {
+        let json = Renderer::new(source_code).render_json(&run(source_code).unwrap_err());
+
+        let expression = &source_code["let s = ".len()..source_code.len() - 1];
+        assert_eq!(json_span_text(source_code, &json), expression);
+    }
+}
+
+#[test]
+fn json_span_on_a_non_ascii_line() {
+    let source_code = "let é = \"ü\" + 1;";
+    let json = Renderer::new(source_code).render_json(&run(source_code).unwrap_err());
+
+    assert_eq!(json_span_text(source_code, &json), "\"ü\" + 1");
+    // Columns count characters rather than bytes
+    assert_eq!(
+        (
+            json_number(&json, "column_start"),
+            json_number(&json, "column_end")
+        ),
+        (9, 15)
+    );
+}
+
+#[test]
+fn json_strings_are_escaped() {
+    let source_code = "print(1 + nil);";
+    let mut diagnostic = run(source_code).unwrap_err();
+    diagnostic.message = String::from("\"quoted\" \\ back\nslash\t\u{1}é");
+    diagnostic.notes.push(String::from("a \"note\""));
+
+    let json = Renderer::new(source_code).render_json(&diagnostic);
+
+    assert!(json.contains(r#""message":"\"quoted\" \\ back\nslash\t\u0001é""#));
+    assert!(json.contains(r#""notes":["a \"note\""]"#));
+    assert!(!json.contains('\n'));
+}
+
+#[test]
+fn render_shows_context_lines() {
+    let source_code = "let x = 1;\nprint(x + nil);\nprint(x);\n";
+    let diagnostic = run(source_code).unwrap_err();
+
+    assert_eq!(
+        Renderer::new(source_code).render(&diagnostic),
+        "\
+[Error M0106]
+Invalid operands of types 'number' and 'nil' for operator '+'
+
+[Location] test.min:2:7
+   |
+ 1 | let x = 1;
+ 2 | print(x + nil);
+   |       ^^^^^^^
+ 3 | print(x);
+"
+    );
+}
+
+#[test]
+fn render_leaves_out_the_middle_of_long_spans() {
+    let source_code = "let t = {\n    a = 1,\n    b = 2,\n\n    c = 3,\n} + 1;\n";
+    let diagnostic = run(source_code).unwrap_err();
+
+    assert_eq!(
+        Renderer::new(source_code).render(&diagnostic),
+        "\
+[Error M0106]
+Invalid operands of types 'table' and 'number' for operator '+'
+
+[Location] test.min:1:9
+   |
+ 1 | let t = {
+   |         ^
+ 2 |     a = 1,
+   |     ^^^^^^
+...
+ 5 |     c = 3,
+   |     ^^^^^^
+ 6 | } + 1;
+   | ^^^^^
+"
+    );
+}
+
+#[test]
+fn render_keeps_tabs_before_the_markers() {
+    let source_code = "fn f() {\n\tlet y = 1 + nil;\n}\nf();\n";
+    let diagnostic = run(source_code).unwrap_err();
+
+    assert_eq!(
+        Renderer::new(source_code).render(&diagnostic),
+        "\
+[Error M0106]
+Invalid operands of types 'number' and 'nil' for operator '+'
+
+[Location] test.min:2:10
+   |
+ 1 | fn f() {
+ 2 | \tlet y = 1 + nil;
+   | \t        ^^^^^^^
+ 3 | }
+
+[Note]
+Call stack:
+ in 'f' called at test.min:4:1
+"
+    );
+}
+
+#[test]
+fn render_shows_where_the_function_is_defined() {
+    let source_code = "fn add(a, b) {\n    return a + b;\n}\nprint(add(1));\n";
+    let diagnostic = run(source_code).unwrap_err();
+
+    assert_eq!(
+        Renderer::new(source_code).render(&diagnostic),
+        "\
+[Error M0104]
+Function 'add' expects 2 argument(s), but 1 were given
+
+[Location] test.min:4:7
+   |
+ 3 | }
+ 4 | print(add(1));
+   |       ^^^^^^
+
+[Location] test.min:1:1
+   |
+ 1 | fn add(a, b) {
+   | -------------- function defined here
+"
+    );
+}
+
+#[test]
+fn render_only_uses_colours_when_asked_to() {
+    let source_code = "pritn(1);";
+    let diagnostic = run(source_code).unwrap_err();
+
+    let plain = Renderer::new(source_code).render(&diagnostic);
+    assert!(!plain.contains('\x1b'));
+    assert!(plain.ends_with("\n[Help]\nDid you mean 'print'?\n"));
+
+    let coloured = Renderer::new(source_code)
+        .with_colour(true)
+        .render(&diagnostic);
+    assert!(coloured.starts_with("\x1b[1;31m[Error M0102]\x1b[0m\n"));
+    assert!(coloured.contains("\x1b[1;36m[Help]\x1b[0m"));
+}
@@ -0,0 +1,136 @@
+use std::{cell::RefCell, rc::Rc};
+
+use minute_lang::{table::Table, value::Value};
+
+fn number(number: usize) -> Value {
+    Value::Number(number as f64)
+}
+
+fn table_with(keys: impl IntoIterator<Item = usize>) -> Table {
+    let mut table = Table::default();
+    for key in keys {
+        table.set(number(key), number(key * 10));
+    }
+
+    table
+}
+
+/// Every key `next` visits starting from `key`, in order
+fn keys_after(table: &Table, key: Value) -> Vec<Value> {
+    let mut keys = Vec::new();
+    let mut key = key;
+    while let Some((next_key, _)) = table.next(&key).expect("key should be in the table") {
+        keys.push(next_key.clone());
+        key = next_key;
+    }
+
+    keys
+}
+
+fn is_border(table: &Table, border: usize) -> bool {
+    (border == 0 || table.get(&number(border)) != Value::Nil)
+        && table.get(&number(border + 1)) == Value::Nil
+}
+
+#[test]
+fn length_is_a_border_with_holes() {
+    for hole in 1..=8 {
+        let mut table = table_with(1..=8);
+        table.set(number(hole), Value::Nil);
+
+        let length = table.length();
+        assert!(
+            is_border(&table, length),
+            "hole {}, length {}",
+            hole,
+            length
+        );
+    }
+
+    let mut table = table_with(1..=8);
+    for key in [2, 3, 5, 7] {
+        table.set(number(key), Value::Nil);
+    }
+    assert!(is_border(&table, table.length()));
+}
+
+#[test]
+fn length_shrinks_after_clearing_the_last_value() {
+    let mut table = table_with(1..=5);
+    assert_eq!(table.length(), 5);
+
+    for length in (0..5).rev() {
+        table.set(number(table.length()), Value::Nil);
+        assert_eq!(table.length(), length);
+    }
+
+    table.set(number(1), number(10));
+    assert_eq!(table.length(), 1);
+}
+
+#[test]
+fn keys_set_out_of_order_move_to_the_array_part() {
+    let table = table_with([3, 2, 5, 1]);
+
+    assert_eq!(table.length(), 3);
+    assert_eq!(
+        keys_after(&table, Value::Nil),
+        vec![number(1), number(2), number(3), number(5)]
+    );
+}
+
+#[test]
+fn next_continues_from_a_cleared_key() {
+    let mut table = table_with(1..=3);
+    table.set(Value::String(String::from("a")), number(1));
+    table.set(Value::String(String::from("b")), number(2));
+
+    table.set(number(2), Value::Nil);
+    assert_eq!(
+        keys_after(&table, number(2)),
+        vec![
+            number(3),
+            Value::String(String::from("a")),
+            Value::String(String::from("b"))
+        ]
+    );
+
+    table.set(Value::String(String::from("a")), Value::Nil);
+    assert_eq!(
+        keys_after(&table, Value::String(String::from("a"))),
+        vec![Value::String(String::from("b"))]
+    );
+
+    assert!(table
+        .next(&Value::String(String::from("missing")))
+        .is_none());
+}
+
+#[test]
+fn cleared_hash_keys_are_removed() {
+    let mut table = Table::default();
+    for key in 0..10_000 {
+        let key = Value::Number(key as f64 + 0.5);
+        table.set(key.clone(), number(1));
+        table.set(key, Value::Nil);
+    }
+    table.set(Value::String(String::from("kept")), number(1));
+
+    assert_eq!(
+        keys_after(&table, Value::Nil),
+        vec![Value::String(String::from("kept"))]
+    );
+    assert_eq!(table.get(&Value::Number(0.5)), Value::Nil);
+}
+
+#[test]
+fn deeply_nested_tables_are_dropped() {
+    let mut list = Value::Nil;
+    for _ in 0..200_000 {
+        let mut table = Table::default();
+        table.set(Value::String(String::from("next")), list);
+        list = Value::Table(Rc::new(RefCell::new(table)));
+    }
+
+    drop(list);
+}